@@ -1,7 +1,10 @@
 use std::collections::VecDeque;
 use std::marker::PhantomData;
 
-use simplicity::{encode, BitWriter, Value};
+use simplicity::jet::{Elements, Jet};
+use simplicity::{encode, BitIter, BitWriter, Cmr, CommitNode, RedeemNode, Value};
+
+use crate::util;
 
 pub trait Stage {}
 pub struct Program;
@@ -14,6 +17,9 @@ impl Stage for IllegalPadding {}
 #[derive(Debug)]
 pub struct BitBuilder<S: Stage> {
     queue: VecDeque<(u64, u8)>,
+    /// Declared bit length of the witness block
+    /// and number of bits written by [`BitBuilder::witness_value`].
+    witness_bits: Option<(usize, usize)>,
     stage: PhantomData<S>,
 }
 
@@ -30,19 +36,33 @@ impl<S: Stage> BitBuilder<S> {
         self
     }
 
+    pub fn assert_byte_aligned(self) -> Self {
+        let n_total_written = self.n_total_written();
+        if !n_total_written.is_multiple_of(8) {
+            panic!("{} bits written, not byte aligned", n_total_written);
+        }
+        self
+    }
+
     pub fn bits_be(mut self, bits: u64, bit_len: u8) -> Self {
         self.queue.push_back((bits, bit_len));
         self
     }
 
     pub fn bytes_be<A: AsRef<[u8]>>(mut self, bytes: A) -> Self {
-        for byte in bytes.as_ref() {
-            self = self.bits_be(u64::from(*byte), 8);
-        }
+        let bytes = bytes.as_ref();
+        let words = bytes_to_words(bytes, bytes.len() * 8);
+        self.queue.extend(words);
         self
     }
 
+    /// Write the positive integer `n`, such as a child offset or a length.
+    ///
+    /// ## Panics
+    ///
+    /// `n` is zero. Simplicity has no encoding of zero.
     pub fn positive_integer(mut self, n: usize) -> Self {
+        assert!(0 < n, "Zero is not a positive integer");
         let mut bytes = Vec::new();
         let mut writer = BitWriter::new(&mut bytes);
         let bit_len = encode::encode_natural(n, &mut writer).expect("I/O to vector never fails");
@@ -64,31 +84,64 @@ impl<S: Stage> BitBuilder<S> {
         self
     }
 
+    /// Delete the last `bit_len` bits that were written.
+    ///
+    /// ## Panics
+    ///
+    /// Fewer than `bit_len` bits were written.
     pub fn delete_bits(mut self, mut bit_len: usize) -> Self {
+        let n_total_written = self.n_total_written();
+        if n_total_written < bit_len {
+            panic!(
+                "Cannot delete {} bits, only {} bits written",
+                bit_len, n_total_written
+            );
+        }
         while bit_len > 0 {
-            if let Some((word, word_len)) = self.queue.pop_back() {
-                if usize::from(word_len) <= bit_len {
-                    // Delete entire word
-                    bit_len = bit_len.saturating_sub(usize::from(word_len));
-                } else {
-                    // Truncate word and put it back
-                    let truncated_word = word >> bit_len;
-                    let truncated_word_len = word_len - bit_len as u8; // cast safety: bit_len < word_len <= u8::MAX
-                    self.queue.push_back((truncated_word, truncated_word_len));
-                    break;
-                }
+            let (word, word_len) = self.queue.pop_back().expect("enough bits are written");
+            if usize::from(word_len) <= bit_len {
+                // Delete entire word
+                bit_len -= usize::from(word_len);
+            } else {
+                // Truncate word and put it back
+                let truncated_word = word >> bit_len;
+                let truncated_word_len = word_len - bit_len as u8; // cast safety: bit_len < word_len <= u8::MAX
+                self.queue.push_back((truncated_word, truncated_word_len));
+                break;
             }
         }
         self
     }
 
-    fn get_bytes(mut self) -> Vec<u8> {
+    /// Flip the bit at the given `offset`.
+    ///
+    /// Bits are indexed in the order of writing, starting at zero,
+    /// which is the order of decoding, see [`util::corrupt_at`].
+    ///
+    /// ## Panics
+    ///
+    /// Fewer than `offset + 1` bits have been written.
+    pub fn flip_bit(mut self, offset: usize) -> Self {
+        let n_total_written = self.n_total_written();
+        if n_total_written <= offset {
+            panic!(
+                "Cannot flip bit {}, only {} bits written",
+                offset, n_total_written
+            );
+        }
+        let bytes = util::corrupt_at(&self.written_bytes(), offset);
+        self.queue = bytes_to_words(&bytes, n_total_written).into();
+        self
+    }
+
+    /// Return the bits written so far, padded with zeroes to a whole byte.
+    fn written_bytes(&self) -> Vec<u8> {
         let mut bytes = Vec::new();
         let mut writer = BitWriter::new(&mut bytes);
 
-        while let Some((bits, len)) = self.queue.pop_front() {
+        for (bits, len) in &self.queue {
             writer
-                .write_bits_be(bits, usize::from(len))
+                .write_bits_be(*bits, usize::from(*len))
                 .expect("I/O to vector never fails");
         }
 
@@ -96,6 +149,10 @@ impl<S: Stage> BitBuilder<S> {
         bytes
     }
 
+    fn get_bytes(self) -> Vec<u8> {
+        self.written_bytes()
+    }
+
     pub fn parser_stops_here(self) -> Vec<u8> {
         self.get_bytes()
     }
@@ -105,11 +162,25 @@ impl BitBuilder<Program> {
     pub fn program_preamble(len: usize) -> Self {
         Self {
             queue: VecDeque::new(),
+            witness_bits: None,
             stage: PhantomData,
         }
         .positive_integer(len)
     }
 
+    /// Write the nodes of the given `program` at the current position,
+    /// without the program length and without the witness block.
+    ///
+    /// rust-simplicity encodes the nodes in canonical order.
+    /// Nodes refer to their children by relative offsets,
+    /// so the program may follow other nodes and may be the child of subsequent nodes.
+    /// Its nodes count towards the program length, see [`program_len`].
+    pub fn embed_program(mut self, program: &RedeemNode<Elements>) -> Self {
+        let (_, words) = encode_nodes(program);
+        self.queue.extend(words);
+        self
+    }
+
     pub fn unit(self) -> Self {
         self.bits_be(0b01001, 5)
     }
@@ -118,7 +189,6 @@ impl BitBuilder<Program> {
         self.bits_be(0b01000, 5)
     }
 
-    #[allow(dead_code)]
     pub fn injl(self, left_offset: usize) -> Self {
         self.bits_be(0b00100, 5).positive_integer(left_offset)
     }
@@ -175,14 +245,69 @@ impl BitBuilder<Program> {
         self.bits_be(0b11, 2).bits_be(bits, bit_len)
     }
 
+    /// Write the given `jet` with the bit encoding of rust-simplicity.
+    ///
+    /// Unlike [`BitBuilder::jet`], this stays correct if jet encodings are renumbered.
+    pub fn named_jet(mut self, jet: Elements) -> Self {
+        let mut bytes = Vec::new();
+        let mut writer = BitWriter::new(&mut bytes);
+        let bit_len = jet.encode(&mut writer).expect("I/O to vector never fails");
+        writer.flush_all().expect("I/O to vector never fails");
+
+        self = self.bits_be(0b11, 2);
+        self.queue.extend(bytes_to_words(&bytes, bit_len));
+        self
+    }
+
     pub fn word(self, depth: usize, value: &Value) -> Self {
         self.bits_be(0b10, 2).positive_integer(depth).value(value)
     }
 
+    /// Write a constant word of depth `depth` whose bytes all equal `byte`.
+    ///
+    /// Unlike [`BitBuilder::word`], this does not need a value, so it is fast for wide words.
+    pub fn repeated_byte_word(mut self, depth: usize, byte: u8) -> Self {
+        let mut bytes = Vec::new();
+        let mut writer = BitWriter::new(&mut bytes);
+        let bit_len = util::encode_const_word_fast(depth, byte, &mut writer)
+            .expect("I/O to vector never fails");
+        writer.flush_all().expect("I/O to vector never fails");
+
+        self = self.bits_be(0b10, 2).positive_integer(depth);
+        self.queue.extend(bytes_to_words(&bytes, bit_len));
+        self
+    }
+
     pub fn witness(self) -> Self {
         self.bits_be(0b0111, 4)
     }
 
+    /// Write an arbitrary node tag of `tag_len` bits, without any payload.
+    ///
+    /// This reaches encodings that no other method writes, such as unassigned jet codes.
+    pub fn raw_node(self, tag_bits: u64, tag_len: u8) -> Self {
+        self.bits_be(tag_bits, tag_len)
+    }
+
+    /// Panic if the nodes written so far do not decode to a program with the given `cmr`.
+    ///
+    /// The program is decoded without witness data, see [`CommitNode::decode`],
+    /// so the program must be well-typed and maximally shared.
+    pub fn assert_decodes_to(self, cmr: Cmr) -> Self {
+        let bytes = BitBuilder::<Program> {
+            queue: self.queue.clone(),
+            witness_bits: None,
+            stage: PhantomData,
+        }
+        .get_bytes();
+        match CommitNode::<Elements>::decode(&mut BitIter::from(bytes.as_slice())) {
+            Ok(program) if program.cmr() == cmr => {}
+            Ok(program) => panic!("Program decodes to CMR {}, not {cmr}", program.cmr()),
+            Err(error) => panic!("Program does not decode, expected CMR {cmr}: {error}"),
+        }
+        self
+    }
+
     pub fn witness_preamble(mut self, len: usize) -> BitBuilder<Witness> {
         self = match len {
             0 => self.bits_be(0b0, 1),
@@ -191,19 +316,85 @@ impl BitBuilder<Program> {
 
         BitBuilder {
             queue: self.queue,
+            witness_bits: Some((len, 0)),
             stage: PhantomData,
         }
     }
 }
 
+impl BitBuilder<Program> {
+    /// Write the witness block that consists of the given values, in order.
+    ///
+    /// The bit length in the preamble is computed from the values.
+    pub fn witness_block<'a, I>(self, values: I) -> BitBuilder<Witness>
+    where
+        I: IntoIterator<Item = &'a Value>,
+    {
+        let block = values.into_iter().fold(
+            BitBuilder::<Witness> {
+                queue: VecDeque::new(),
+                witness_bits: None,
+                stage: PhantomData,
+            },
+            |block, value| block.value(value),
+        );
+        let len = block.n_total_written();
+        let mut builder = self.witness_preamble(len);
+        builder.queue.extend(block.queue);
+        builder.witness_bits = Some((len, len));
+        builder
+    }
+}
+
 impl BitBuilder<Witness> {
+    /// Write the given witness `value` to the witness block.
+    ///
+    /// If any value is written this way, then finishing the program checks that
+    /// the witness values fill the declared length of the witness block.
+    pub fn witness_value(mut self, value: &Value) -> Self {
+        let n_before = self.n_total_written();
+        self = self.value(value);
+        let n_value = self.n_total_written() - n_before;
+        let (_, n_written) = self
+            .witness_bits
+            .as_mut()
+            .expect("witness preamble is written");
+        *n_written += n_value;
+        self
+    }
+
+    /// Finish the program.
+    ///
+    /// ## Panics
+    ///
+    /// Values were written with [`BitBuilder::witness_value`],
+    /// but they do not fill the declared length of the witness block.
+    /// Use [`BitBuilder::parser_stops_here`] to finish a witness block of the wrong length.
     pub fn program_finished(self) -> Vec<u8> {
+        if let Some((declared, n_written)) = self.witness_bits {
+            if 0 < n_written && declared != n_written {
+                panic!(
+                    "Witness block declares {} bits, but witness values have {} bits",
+                    declared, n_written
+                );
+            }
+        }
         self.parser_stops_here()
     }
 
+    /// Finish the program and append the given `trailing` bytes after the padding.
+    ///
+    /// Any trailing byte makes the encoding malleable, whatever its content.
+    pub fn program_finished_with_trailing(self, trailing: &[u8]) -> Vec<u8> {
+        let mut bytes = self.program_finished();
+        bytes.extend_from_slice(trailing);
+        bytes
+    }
+
     pub fn illegal_padding(self) -> BitBuilder<IllegalPadding> {
         BitBuilder {
             queue: self.queue,
+            witness_bits: None,
             stage: PhantomData,
         }
     }
@@ -214,13 +405,47 @@ impl BitBuilder<Witness> {
 ///
 /// Each word in front of the final word is 64 bits long.
 /// The final word is between 1 and 64 bits long.
+/// Return the number of nodes that [`BitBuilder::embed_program`] writes for the given `program`.
+pub fn program_len(program: &RedeemNode<Elements>) -> usize {
+    let (len, _) = encode_nodes(program);
+    len
+}
+
+/// Encode the given `program` without its witness block.
+///
+/// Return the number of nodes and the bits of the nodes, without the program length.
+fn encode_nodes(program: &RedeemNode<Elements>) -> (usize, Vec<(u64, u8)>) {
+    let mut bytes = Vec::new();
+    let mut writer = BitWriter::new(&mut bytes);
+    let bit_len = encode::encode_program(program, &mut writer).expect("I/O to vector never fails");
+    writer.flush_all().expect("I/O to vector never fails");
+
+    let mut bits = BitIter::from(bytes.as_slice());
+    let len = bits
+        .read_natural(None)
+        .expect("program starts with its length");
+    let mut words = Vec::new();
+    let mut remaining = bit_len - bits.n_total_read();
+    while 0 < remaining {
+        let word_len = remaining.min(64);
+        let word = (0..word_len).fold(0u64, |word, _| {
+            let bit = bits.read_bit().expect("bits are within the encoding");
+            (word << 1) | u64::from(bit)
+        });
+        words.push((word, word_len as u8)); // cast safety: word_len <= 64
+        remaining -= word_len;
+    }
+    (len, words)
+}
+
 fn bytes_to_words(bytes: &[u8], mut bit_len: usize) -> Vec<(u64, u8)> {
     assert!(
         bit_len <= bytes.len() * 8,
         "Bit length points past end of byte string"
     );
 
-    if bytes.is_empty() || bit_len == 0 {
+    // Zero bits make zero words, even if there are bytes
+    if bit_len == 0 {
         return Vec::new();
     }
 
@@ -232,10 +457,12 @@ fn bytes_to_words(bytes: &[u8], mut bit_len: usize) -> Vec<(u64, u8)> {
         word += u64::from(*byte);
 
         if bit_len <= 8 {
+            // The final byte has at least one bit, because bit_len > 0 is checked above
+            // and bit_len > 8 before each subtraction below
+            debug_assert!(0 < bit_len);
             if bit_len < 8 {
                 // Final bits are less than one byte
                 // Shift word to the right and pad zeroes from front
-                // This even works if bit_len = 0, in which case the most recent byte is erased
                 word >>= 8 - bit_len;
             }
 
@@ -266,3 +493,320 @@ fn bytes_to_words(bytes: &[u8], mut bit_len: usize) -> Vec<(u64, u8)> {
 
     words
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn empty_builder() -> BitBuilder<Witness> {
+        BitBuilder {
+            queue: VecDeque::new(),
+            witness_bits: None,
+            stage: PhantomData,
+        }
+    }
+
+    fn unit_iden_program() -> BitBuilder<Program> {
+        // 1 + 5 + 5 + 9 = 20 bits
+        BitBuilder::program_preamble(1).unit().iden().comp(2, 1)
+    }
+
+    #[test]
+    fn byte_aligned() {
+        unit_iden_program().bits_be(0, 4).assert_byte_aligned();
+    }
+
+    #[test]
+    fn named_jet_matches_raw_jet() {
+        let named = BitBuilder::program_preamble(1)
+            .named_jet(Elements::Version)
+            .get_bytes();
+        let raw = BitBuilder::program_preamble(1).jet(462384, 19).get_bytes();
+        assert_eq!(raw, named);
+    }
+
+    #[test]
+    fn embedded_program_matches_encoding() {
+        let s = "main := comp (comp (pair (const 0x01) (const 0x02)) jet_lt_8) jet_verify";
+        let program = util::program_from_string(s, &std::collections::HashMap::new());
+        let bytes = BitBuilder::program_preamble(program_len(&program))
+            .embed_program(&program)
+            .assert_decodes_to(program.cmr())
+            .witness_preamble(0)
+            .program_finished();
+        assert_eq!(program.encode_to_vec(), bytes);
+    }
+
+    #[test]
+    fn embedded_program_after_prefix() {
+        let s = "main := comp (const 0b1) jet_verify";
+        let program = util::program_from_string(s, &std::collections::HashMap::new());
+        let len = program_len(&program);
+        // comp unit program
+        BitBuilder::program_preamble(len + 2)
+            .unit()
+            .embed_program(&program)
+            .comp(len + 1, 1)
+            .assert_decodes_to(Cmr::comp(Cmr::unit(), program.cmr()));
+    }
+
+    #[test]
+    fn flip_bit_across_words() {
+        let bytes = [0x00, 0xff, 0x00, 0xff, 0x00, 0xff, 0x00, 0xff, 0x00];
+        for offset in [0, 9, 63, 64, 71] {
+            let flipped = empty_builder().bytes_be(bytes).flip_bit(offset).get_bytes();
+            assert_eq!(util::corrupt_at(&bytes, offset), flipped);
+        }
+    }
+
+    #[test]
+    fn flip_bit_keeps_bit_length() {
+        let bytes = empty_builder()
+            .bits_be(0b101, 3)
+            .flip_bit(1)
+            .bits_be(0b1, 1)
+            .assert_n_total_written(4)
+            .get_bytes();
+        assert_eq!(vec![0b1111_0000], bytes);
+    }
+
+    #[test]
+    fn flip_bit_of_unit_program() {
+        let builder = BitBuilder::program_preamble(1)
+            .unit()
+            .witness_preamble(0)
+            .flip_bit(0);
+        let bytes = builder.program_finished();
+        let mut bits = simplicity::BitIter::from(bytes.into_iter());
+        // The program length prefix no longer ends after its first bit,
+        // so the parser reads past the end of the stream
+        assert!(matches!(
+            simplicity::RedeemNode::<Elements>::decode(&mut bits),
+            Err(simplicity::Error::Decode(
+                simplicity::decode::Error::EndOfStream
+            ))
+        ));
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot flip bit 7, only 7 bits written")]
+    fn flip_bit_past_end() {
+        BitBuilder::program_preamble(1)
+            .unit()
+            .witness_preamble(0)
+            .flip_bit(7);
+    }
+
+    #[test]
+    fn witness_value_fills_block() {
+        let value = Value::u8(0xab);
+        let bytes = BitBuilder::program_preamble(1)
+            .witness()
+            .witness_preamble(8)
+            .witness_value(&value)
+            .program_finished();
+        let expected = BitBuilder::program_preamble(1)
+            .witness()
+            .witness_block([value.as_ref()])
+            .program_finished();
+        assert_eq!(expected, bytes);
+    }
+
+    #[test]
+    #[should_panic(expected = "Witness block declares 9 bits, but witness values have 8 bits")]
+    fn witness_value_does_not_fill_block() {
+        BitBuilder::program_preamble(1)
+            .witness()
+            .witness_preamble(9)
+            .witness_value(&Value::u8(0xab))
+            .program_finished();
+    }
+
+    #[test]
+    fn positive_integer_bits() {
+        for (n, bits, bit_len) in [
+            (1, 0b0, 1),
+            (2, 0b100, 3),
+            (3, 0b101, 3),
+            (4, 0b110000, 6),
+            (5, 0b110001, 6),
+        ] {
+            let builder = empty_builder().positive_integer(n);
+            assert_eq!(vec![(bits, bit_len)], Vec::from(builder.queue), "{n}");
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Zero is not a positive integer")]
+    fn positive_integer_zero() {
+        empty_builder().positive_integer(0);
+    }
+
+    #[test]
+    fn decodes_to_cmr() {
+        BitBuilder::program_preamble(3)
+            .unit()
+            .iden()
+            .comp(2, 1)
+            .assert_decodes_to(Cmr::comp(Cmr::unit(), Cmr::iden()));
+    }
+
+    #[test]
+    #[should_panic(expected = "Program decodes to CMR")]
+    fn decodes_to_other_cmr() {
+        BitBuilder::program_preamble(2)
+            .unit()
+            .comp(1, 1)
+            .assert_decodes_to(Cmr::comp(Cmr::unit(), Cmr::iden()));
+    }
+
+    #[test]
+    #[should_panic(expected = "Program does not decode")]
+    fn does_not_decode() {
+        BitBuilder::program_preamble(2)
+            .unit()
+            .assert_decodes_to(Cmr::unit());
+    }
+
+    #[test]
+    fn bytes_to_words_zero_bits() {
+        assert!(bytes_to_words(&[], 0).is_empty());
+        assert!(bytes_to_words(&[0xab], 0).is_empty());
+    }
+
+    #[test]
+    fn bytes_to_words_partial_byte() {
+        assert_eq!(vec![(0xa, 4)], bytes_to_words(&[0xab, 0xcd], 4));
+        assert_eq!(vec![(0xab, 8)], bytes_to_words(&[0xab, 0xcd], 8));
+        assert_eq!(vec![(0xabc, 12)], bytes_to_words(&[0xab, 0xcd], 12));
+    }
+
+    #[test]
+    fn bytes_to_words_multiple_words() {
+        let bytes = [0xff; 9];
+        assert_eq!(vec![(u64::MAX, 64), (0x1, 1)], bytes_to_words(&bytes, 65));
+    }
+
+    #[test]
+    fn delete_bits_across_words() {
+        let builder = BitBuilder::program_preamble(1)
+            .bytes_be([0xff; 9])
+            .bits_be(0b101, 3)
+            .delete_bits(3 + 8 + 8);
+        // 1 + 56 bits remain
+        assert_eq!(
+            vec![0b0111_1111, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0b1000_0000],
+            builder.assert_n_total_written(57).parser_stops_here()
+        );
+    }
+
+    #[test]
+    fn delete_bits_empties_queue() {
+        let builder = BitBuilder::program_preamble(1)
+            .bytes_be([0xff; 9])
+            .delete_bits(1 + 72);
+        assert!(builder
+            .assert_n_total_written(0)
+            .parser_stops_here()
+            .is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot delete 74 bits, only 73 bits written")]
+    fn delete_bits_underflow() {
+        BitBuilder::program_preamble(1)
+            .bytes_be([0xff; 9])
+            .delete_bits(74);
+    }
+
+    #[test]
+    fn raw_node_unit() {
+        let raw = BitBuilder::program_preamble(1)
+            .raw_node(0b01001, 5)
+            .iden()
+            .comp(2, 1);
+        assert_eq!(
+            unit_iden_program().parser_stops_here(),
+            raw.parser_stops_here()
+        );
+    }
+
+    /// Encode the same programs with the bit builder and with the library,
+    /// and compare the bytes and the time that each takes.
+    #[test]
+    fn matches_library_encoder() {
+        use std::collections::HashMap;
+        use std::time::{Duration, Instant};
+
+        const N_ROUNDS: u32 = 1_000;
+
+        type Build<'a> = Box<dyn Fn() -> Vec<u8> + 'a>;
+
+        fn time<F: Fn() -> Vec<u8>>(f: F) -> (Vec<u8>, Duration) {
+            let start = Instant::now();
+            for _ in 1..N_ROUNDS {
+                f();
+            }
+            let bytes = f();
+            (bytes, start.elapsed() / N_ROUNDS)
+        }
+
+        let mut ladder = "cp0 := iden\n".to_string();
+        for i in 1..=21 {
+            ladder.push_str(&format!("cp{i} := comp cp{} cp{}\n", i - 1, i - 1));
+        }
+        ladder.push_str("main := cp21");
+        let word = Value::u16(0xbeef);
+
+        let programs: [(&str, &str, Build); 3] = [
+            (
+                "unit",
+                "main := unit",
+                Box::new(|| {
+                    BitBuilder::program_preamble(1)
+                        .unit()
+                        .witness_preamble(0)
+                        .program_finished()
+                }),
+            ),
+            (
+                "comp ladder",
+                &ladder,
+                Box::new(|| {
+                    (0..21)
+                        .fold(BitBuilder::program_preamble(22).iden(), |builder, _| {
+                            builder.comp(1, 1)
+                        })
+                        .witness_preamble(0)
+                        .program_finished()
+                }),
+            ),
+            (
+                "word",
+                "main := comp (const 0xbeef) unit",
+                Box::new(|| {
+                    BitBuilder::program_preamble(3)
+                        .word(5, &word)
+                        .unit()
+                        .comp(2, 1)
+                        .witness_preamble(0)
+                        .program_finished()
+                }),
+            ),
+        ];
+
+        for (name, s, build) in programs {
+            let program = crate::util::program_from_string(s, &HashMap::new());
+            let (library_bytes, library_time) = time(|| program.encode_to_vec());
+            let (builder_bytes, builder_time) = time(build);
+            println!("{name}: library {library_time:?}, bit builder {builder_time:?}");
+            assert_eq!(library_bytes, builder_bytes, "{name}: encodings differ");
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "20 bits written, not byte aligned")]
+    fn not_byte_aligned() {
+        unit_iden_program().assert_byte_aligned();
+    }
+}
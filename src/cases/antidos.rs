@@ -0,0 +1,190 @@
+//! # Anti-DoS checks
+//!
+//! Programs whose executed branches are not as the anti-DoS checks require.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use simplicity::jet::Elements;
+use simplicity::{RedeemNode, Value};
+
+use super::Deferred;
+use crate::json::{ScriptError, TestCase};
+use crate::test::TestBuilder;
+use crate::util::{self, Case};
+use crate::witness::WitnessBuilder;
+
+/// Return the default test cases of this category.
+pub fn cases() -> impl Iterator<Item = TestCase> {
+    let mut test_cases: Vec<Deferred> = Vec::new();
+
+    /*
+     * Unexecuted branches must be hidden via assertions (antidos)
+     *
+     * Executing the left branch of a right assertion leads to failure (assert)
+     *
+     * Executing the right branch of a left assertion leads to failure (assert)
+     */
+    /// Program where some nodes are unexecuted.
+    /// If go_right is false, then the left case child is executed and the right child stays unexecuted.
+    /// Vice versa for go_right being true.
+    fn some_unexecuted_case_program(case: Case, go_right: bool) -> Arc<RedeemNode<Elements>> {
+        // No node is shared
+        let s = format!(
+            "
+            input := pair (const 0b{}) unit
+            main := comp input ({} {} {})
+        ",
+            u8::from(go_right),
+            case,
+            case.left_child("unit"),
+            case.right_child("take iden")
+        );
+
+        let empty_witness = HashMap::new();
+        util::program_from_string(s.as_str(), &empty_witness)
+    }
+
+    for case in Case::all() {
+        for go_right in [true, false] {
+            test_cases.push(Box::new(move || {
+                let error = match case {
+                    Case::Both => ScriptError::SimplicityAntidos,
+                    Case::Left if go_right => ScriptError::SimplicityExecAssert,
+                    Case::Right if !go_right => ScriptError::SimplicityExecAssert,
+                    _ => ScriptError::Ok,
+                };
+                let comment = format!(
+                    "antidos/some_unexecuted_{}_go_{}",
+                    case,
+                    if go_right { "right" } else { "left" }
+                );
+                TestBuilder::comment(comment)
+                    .description("Unexecuted branches must be hidden via assertions (antidos)")
+                    .program(&some_unexecuted_case_program(case, go_right))
+                    .expected_error(error)
+                    .finished()
+            }));
+        }
+    }
+
+    /*
+     * A child of case must be executed by case itself,
+     * even if the child is executed by a different parent in the DAG
+     */
+    /// Program where all nodes are executed.
+    /// If go_right is false, then the case node will execute the left child.
+    /// The right case child will be left unexecuted.
+    /// If go_right is true, then the left case child is left unexecuted.
+    fn all_executed_case_program(case: Case, go_right: bool) -> Arc<RedeemNode<Elements>> {
+        // Problem is the only shared node
+        let s = format!(
+            "
+            input := pair (const 0b{}) unit
+            problem := unit : 1 * 1 -> 1
+            main := comp input ({} {} {})
+        ",
+            u8::from(go_right),
+            case,
+            case.left_child("problem"),
+            case.right_child("problem")
+        );
+
+        let empty_witness = HashMap::new();
+        util::program_from_string(s.as_str(), &empty_witness)
+    }
+
+    for case in Case::all() {
+        for go_right in [true, false] {
+            test_cases.push(Box::new(move || {
+                let error = match case {
+                    Case::Both => ScriptError::SimplicityAntidos,
+                    Case::Left if go_right => ScriptError::SimplicityExecAssert,
+                    Case::Right if !go_right => ScriptError::SimplicityExecAssert,
+                    _ => ScriptError::Ok,
+                };
+                let comment = format!(
+                    "antidos/all_executed_{}_go_{}",
+                    case,
+                    if go_right { "right" } else { "left" }
+                );
+                TestBuilder::comment(comment)
+                    .description("A child of case must be executed by case itself")
+                    .program(&all_executed_case_program(case, go_right))
+                    .expected_error(error)
+                    .finished()
+            }));
+        }
+    }
+
+    /*
+     * Each case variant succeeds if only its live branches are executed
+     *
+     * - assertl: selector 0 executes the left branch; the right branch is hidden
+     * - assertr: selector 1 executes the right branch; the left branch is hidden
+     * - case: selectors 0 and 1 execute both branches, one after the other
+     */
+    for case in Case::all() {
+        test_cases.push(Box::new(move || {
+            let branch = format!(
+                "{} {} {}",
+                case,
+                case.left_child("unit"),
+                case.right_child("take iden")
+            );
+            let main = match case {
+                Case::Left => "comp go_left branch",
+                Case::Right => "comp go_right branch",
+                Case::Both => "comp (pair (comp go_left branch) (comp go_right branch)) unit",
+            };
+            let s = format!(
+                "
+                go_left := pair (const 0b0) unit
+                go_right := pair (const 0b1) unit
+                branch := {branch}
+                main := {main}
+            "
+            );
+            TestBuilder::comment(format!("ok/{case}_live_branches"))
+                .description("Each case variant succeeds if only its live branches are executed")
+                .human_encoding(&s, &HashMap::new())
+                .expected_error(ScriptError::Ok)
+                .finished()
+        }));
+    }
+
+    /*
+     * Case with both branches present, selected by a witness bit
+     *
+     * The left branch outputs 0 and the right branch outputs 1,
+     * so `branch` reproduces its input bit iff case takes the branch of the selector.
+     * A single run of `case` with a witness selector leaves one branch unexecuted (antidos).
+     * Therefore, `check` runs `case` on the selector and on its complement,
+     * and the selector only decides which branch is taken first:
+     *
+     * - Selector 0: left branch, then right branch: OK
+     * - Selector 1: right branch, then left branch: OK
+     */
+    for selector in [0, 1] {
+        test_cases.push(Box::new(move || {
+            let s = "
+                sel := witness
+                left := comp unit (const 0b0)
+                right := comp unit (const 0b1)
+                branch := comp (pair iden unit) (case left right)
+                check := comp (comp (pair branch iden) jet_eq_1) jet_verify
+                main := comp sel (comp (pair check (comp jet_complement_1 check)) unit)
+            ";
+            let witness = WitnessBuilder::new()
+                .value("sel", Value::u1(selector))
+                .to_map();
+            TestBuilder::comment(format!("ok/case_both_branches_selector_{selector}"))
+                .description("Case with both branches present, selected by a witness bit")
+                .human_encoding(s, &witness)
+                .expected_error(ScriptError::Ok)
+                .finished()
+        }));
+    }
+
+    super::generate(test_cases)
+}
@@ -0,0 +1,278 @@
+//! # Bitstream EOF
+//!
+//! Programs whose encoding ends before the parser is done.
+
+use std::collections::HashMap;
+
+use simplicity::jet::Elements;
+use simplicity::{Cmr, Value};
+
+use super::Deferred;
+use crate::bit_encoding::{self, BitBuilder};
+use crate::json::{ScriptError, TestCase};
+use crate::test::TestBuilder;
+use crate::util;
+
+/// Return the default test cases of this category.
+pub fn cases() -> impl Iterator<Item = TestCase> {
+    let mut test_cases: Vec<Deferred> = Vec::new();
+
+    /*
+     * Empty program
+     */
+    test_cases.push(Box::new(|| {
+        TestBuilder::comment("bitstream_eof/empty_program")
+            .description("Empty program")
+            .raw_program(vec![])
+            .raw_cmr([0; 32])
+            .expected_error(ScriptError::SimplicityBitstreamEof)
+            .finished()
+    }));
+
+    /*
+     * Empty program with a valid CMR
+     *
+     * The CMR is checked only after the program has been parsed successfully.
+     * There is nothing to parse, so the CMR of `unit` makes no difference.
+     */
+    test_cases.push(Box::new(|| {
+        TestBuilder::comment("bitstream_eof/empty_program_valid_cmr")
+            .description("Empty program with a valid CMR")
+            .raw_program(vec![])
+            .raw_cmr(Cmr::unit())
+            .expected_error(ScriptError::SimplicityBitstreamEof)
+            .finished()
+    }));
+
+    /*
+     * Unfinished program length
+     */
+    test_cases.push(Box::new(|| {
+        let bytes = BitBuilder::program_preamble(16)
+            .assert_n_total_written(8 + 3)
+            .delete_bits(3)
+            .parser_stops_here();
+        TestBuilder::comment("bitstream_eof/unfinished_program_length")
+            .description("Unfinished program length")
+            .raw_program(bytes)
+            .raw_cmr([0; 32])
+            .expected_error(ScriptError::SimplicityBitstreamEof)
+            .finished()
+    }));
+
+    /*
+     * Unfinished combinator body
+     */
+    test_cases.push(Box::new(|| {
+        let bytes = BitBuilder::program_preamble(3)
+            .unit()
+            .iden()
+            .comp(2, 1)
+            .assert_n_total_written(2 * 8 + 6)
+            .delete_bits(6)
+            .parser_stops_here();
+        let cmr = Cmr::case(Cmr::unit(), Cmr::iden());
+        TestBuilder::comment("bitstream_eof/unfinished_combinator_body")
+            .description("Unfinished combinator body")
+            .raw_program(bytes)
+            .raw_cmr(cmr)
+            .expected_error(ScriptError::SimplicityBitstreamEof)
+            .finished()
+    }));
+
+    /*
+     * Unfinished combinator child index
+     */
+    test_cases.push(Box::new(|| {
+        let bytes = BitBuilder::program_preamble(4) // Increase len for more bits
+            .unit()
+            .iden()
+            .comp(2, 1)
+            .assert_n_total_written(3 * 8 + 1)
+            .delete_bits(1)
+            .parser_stops_here();
+        let cmr = Cmr::comp(Cmr::unit(), Cmr::iden());
+        TestBuilder::comment("bitstream_eof/unfinished_combinator_child_index")
+            .description("Unfinished combinator child index")
+            .raw_program(bytes)
+            .raw_cmr(cmr)
+            .expected_error(ScriptError::SimplicityBitstreamEof)
+            .finished()
+    }));
+
+    /*
+     * Finished combinator body + child indices
+     */
+    test_cases.push(Box::new(|| {
+        let bytes = BitBuilder::program_preamble(3)
+            .unit()
+            .iden()
+            .comp(2, 1)
+            .witness_preamble(0)
+            .program_finished();
+        let cmr = Cmr::comp(Cmr::unit(), Cmr::iden());
+        TestBuilder::comment("bitstream_eof/finished_combinator")
+            .description("Finished combinator body + child indices")
+            .raw_program(bytes)
+            .raw_cmr(cmr)
+            .expected_error(ScriptError::Ok)
+            .finished()
+    }));
+
+    /*
+     * Unfinished witness length
+     */
+    test_cases.push(Box::new(|| {
+        let bytes = BitBuilder::program_preamble(1)
+            .unit()
+            .witness_preamble(16)
+            .assert_n_total_written(2 * 8 + 2)
+            .delete_bits(2)
+            .parser_stops_here();
+        let cmr = Cmr::unit();
+        TestBuilder::comment("bitstream_eof/unfinished_witness_length")
+            .description("Unfinished witness length")
+            .raw_program(bytes)
+            .raw_cmr(cmr)
+            .expected_error(ScriptError::SimplicityBitstreamEof)
+            .finished()
+    }));
+
+    /*
+     * Unfinished witness block
+     */
+    test_cases.push(Box::new(|| {
+        let bytes = BitBuilder::program_preamble(1)
+            .unit()
+            .witness_preamble(1)
+            .bits_be(u64::default(), 0) // No bits means we declared too many
+            .parser_stops_here();
+        let cmr = Cmr::unit();
+        TestBuilder::comment("bitstream_eof/unfinished_witness_block")
+            .description("Unfinished witness block")
+            .raw_program(bytes)
+            .raw_cmr(cmr)
+            .expected_error(ScriptError::SimplicityBitstreamEof)
+            .finished()
+    }));
+
+    /*
+     * Unfinished witness block (C test vector)
+     */
+    test_cases.push(Box::new(|| {
+        let bytes = BitBuilder::program_preamble(1)
+            .unit()
+            .witness_preamble((1 << 31) - 1)
+            .bits_be(u64::default(), 0) // No bits means we declared too many
+            .parser_stops_here();
+        let cmr = Cmr::unit();
+        TestBuilder::comment("bitstream_eof/unfinished_witness_block2")
+            .description("Unfinished witness block (C test vector)")
+            .raw_program(bytes)
+            .raw_cmr(cmr)
+            .expected_error(ScriptError::SimplicityBitstreamEof)
+            .finished()
+    }));
+
+    /*
+     * Embedded program ends in the middle of its root
+     *
+     * The program is built in the human encoding and embedded as it is.
+     * The bits after the last full byte are deleted, so the root is unfinished.
+     * The program ends on a byte boundary, so no padding bits complete the root.
+     */
+    test_cases.push(Box::new(|| {
+        let s = "main := comp (comp (pair (const 0x01) (const 0x02)) jet_lt_8) jet_verify";
+        let program = util::program_from_string(s, &HashMap::new());
+        let mut sink = simplicity::BitWriter::new(std::io::sink());
+        let bit_len = simplicity::encode::encode_program(&program, &mut sink)
+            .expect("I/O to sink never fails");
+        assert_ne!(0, bit_len % 8, "Program must end in the middle of a byte");
+        let bytes = BitBuilder::program_preamble(bit_encoding::program_len(&program))
+            .embed_program(&program)
+            .delete_bits(bit_len % 8)
+            .assert_byte_aligned()
+            .parser_stops_here();
+        TestBuilder::comment("bitstream_eof/embedded_program_unfinished_root")
+            .description("Embedded program ends in the middle of its root")
+            .raw_program(bytes)
+            .raw_cmr(program.cmr())
+            .expected_error(ScriptError::SimplicityBitstreamEof)
+            .finished()
+    }));
+
+    /*
+     * Unfinished jet body
+     */
+    test_cases.push(Box::new(|| {
+        let bytes = BitBuilder::program_preamble(3)
+            .named_jet(Elements::Version)
+            .assert_n_total_written(3 * 8)
+            .delete_bits(8)
+            .parser_stops_here();
+        let cmr = Cmr::comp(Cmr::jet(Elements::Version), Cmr::unit());
+        TestBuilder::comment("bitstream_eof/unfinished_jet_body")
+            .description("Unfinished jet body")
+            .raw_program(bytes)
+            .raw_cmr(cmr)
+            .expected_error(ScriptError::SimplicityBitstreamEof)
+            .finished()
+    }));
+
+    /*
+     * Finished jet body
+     */
+    test_cases.push(Box::new(|| {
+        let bytes = BitBuilder::program_preamble(3)
+            .named_jet(Elements::Version)
+            .unit()
+            .comp(2, 1)
+            .witness_preamble(0)
+            .program_finished();
+        let cmr = Cmr::comp(Cmr::jet(Elements::Version), Cmr::unit());
+        TestBuilder::comment("bitstream_eof/finished_jet_body")
+            .description("Finished jet body")
+            .raw_program(bytes)
+            .raw_cmr(cmr)
+            .expected_error(ScriptError::Ok)
+            .finished()
+    }));
+
+    /*
+     * Unfinished word
+     */
+    // Program that causes SIMPLICITY_BITSTREAM_EOF iff a non-64-bit value is passed
+    fn unfinished_word_program(value: &Value) -> (Vec<u8>, Cmr) {
+        let bytes = BitBuilder::program_preamble(3)
+            .word(7, value)
+            .unit()
+            .comp(2, 1)
+            .witness_preamble(0)
+            .program_finished();
+        let cmr = Cmr::comp(Cmr::const_word(value), Cmr::unit());
+        (bytes, cmr)
+    }
+
+    test_cases.push(Box::new(|| {
+        let value = Value::u1(0);
+        TestBuilder::comment("bitstream_eof/unfinished_word")
+            .description("Unfinished word")
+            .raw_program_cmr(unfinished_word_program(&value))
+            .expected_error(ScriptError::SimplicityBitstreamEof)
+            .finished()
+    }));
+
+    /*
+     * Finished word
+     */
+    test_cases.push(Box::new(|| {
+        let value = Value::u64(u64::MAX);
+        TestBuilder::comment("bitstream_eof/finished_word")
+            .description("Finished word")
+            .raw_program_cmr(unfinished_word_program(&value))
+            .expected_error(ScriptError::Ok)
+            .finished()
+    }));
+
+    super::generate(test_cases)
+}
@@ -0,0 +1,207 @@
+//! # Unused bits and bytes
+//!
+//! Programs that are followed by trailing bytes or by illegal padding.
+
+use simplicity::jet::Elements;
+use simplicity::Cmr;
+
+use super::Deferred;
+use crate::bit_encoding::BitBuilder;
+use crate::json::{ScriptError, TestCase};
+use crate::test::TestBuilder;
+
+/// Return the default test cases of this category.
+pub fn cases() -> impl Iterator<Item = TestCase> {
+    let mut test_cases: Vec<Deferred> = Vec::new();
+
+    /*
+     * Trailing bytes after program encoding (malleability)
+     */
+    /// Program `unit` followed by the given `trailing` bytes.
+    ///
+    /// The program causes SIMPLICITY_BITSTREAM_UNUSED_BYTES iff there are trailing bytes.
+    fn trailing_bytes_program(trailing: &[u8]) -> (Vec<u8>, Cmr) {
+        let bytes = BitBuilder::program_preamble(1)
+            .unit()
+            .witness_preamble(0)
+            .program_finished_with_trailing(trailing);
+        (bytes, Cmr::unit())
+    }
+
+    test_cases.push(Box::new(|| {
+        TestBuilder::comment("bitstream_trailing_bytes/trailing_bytes")
+            .description("Trailing bytes after program encoding (malleability)")
+            .raw_program_cmr(trailing_bytes_program(&[0x00]))
+            .expected_error(ScriptError::SimplicityBitstreamUnusedBytes)
+            .finished()
+    }));
+
+    /*
+     * No trailing bytes after program encoding
+     */
+    test_cases.push(Box::new(|| {
+        TestBuilder::comment("bitstream_trailing_bytes/no_trailing_bytes")
+            .description("No trailing bytes after program encoding")
+            .raw_program_cmr(trailing_bytes_program(&[]))
+            .expected_error(ScriptError::Ok)
+            .finished()
+    }));
+
+    /*
+     * Trailing bytes are rejected regardless of their content (malleability)
+     *
+     * The program is hand-built and uses a jet, so it is longer than one byte.
+     * The trailing bytes are either all zero or all non-zero.
+     */
+    for (trailing, comment) in [
+        (
+            &[0x00, 0x00][..],
+            "bitstream_trailing_bytes/jet_program_trailing_zero_bytes",
+        ),
+        (
+            &[0xde, 0xad][..],
+            "bitstream_trailing_bytes/jet_program_trailing_nonzero_bytes",
+        ),
+    ] {
+        test_cases.push(Box::new(move || {
+            let bytes = BitBuilder::program_preamble(3)
+                .named_jet(Elements::Version)
+                .unit()
+                .comp(2, 1)
+                .witness_preamble(0)
+                .program_finished_with_trailing(trailing);
+            let cmr = Cmr::comp(Cmr::jet(Elements::Version), Cmr::unit());
+            TestBuilder::comment(comment)
+                .description(
+                    "Trailing bytes are rejected regardless of their content (malleability)",
+                )
+                .raw_program(bytes)
+                .raw_cmr(cmr)
+                .expected_error(ScriptError::SimplicityBitstreamUnusedBytes)
+                .finished()
+        }));
+    }
+
+    /*
+     * Illegal padding in final program byte (malleability)
+     */
+    /// Program causes SIMPLICITY_BITSTREAM_UNUSED_BITS iff pad_with = true
+    fn illegal_padding_program(pad_with: bool) -> (Vec<u8>, Cmr) {
+        let bytes = BitBuilder::program_preamble(1)
+            .unit()
+            .witness_preamble(0)
+            .illegal_padding()
+            .bits_be(u64::from(pad_with), 1)
+            .assert_n_total_written(8)
+            .parser_stops_here();
+        let cmr = Cmr::unit();
+        (bytes, cmr)
+    }
+
+    test_cases.push(Box::new(|| {
+        TestBuilder::comment("bitstream_illegal_padding/illegal_padding")
+            .description("Illegal padding in final program byte (malleability)")
+            .raw_program_cmr(illegal_padding_program(true))
+            .expected_error(ScriptError::SimplicityBitstreamUnusedBits)
+            .finished()
+    }));
+
+    /*
+     * Legal padding in final program byte
+     */
+    test_cases.push(Box::new(|| {
+        TestBuilder::comment("bitstream_illegal_padding/legal_padding")
+            .description("Legal padding in final program byte")
+            .raw_program_cmr(illegal_padding_program(false))
+            .expected_error(ScriptError::Ok)
+            .finished()
+    }));
+
+    /*
+     * Illegal padding in final program byte, followed by a trailing byte
+     *
+     * The validator checks for trailing bytes before it checks the padding,
+     * so SIMPLICITY_BITSTREAM_UNUSED_BYTES takes precedence over SIMPLICITY_BITSTREAM_UNUSED_BITS.
+     */
+    test_cases.push(Box::new(|| {
+        let bytes = BitBuilder::program_preamble(1)
+            .unit()
+            .witness_preamble(0)
+            .illegal_padding()
+            .bits_be(0b1, 1)
+            .assert_n_total_written(8)
+            .bits_be(0x00, 8) // trailing byte
+            .parser_stops_here();
+        TestBuilder::comment("bitstream_trailing_bytes/illegal_padding_and_trailing_bytes")
+            .description("Illegal padding in final program byte, followed by a trailing byte")
+            .raw_program(bytes)
+            .raw_cmr(Cmr::unit())
+            .expected_error(ScriptError::SimplicityBitstreamUnusedBytes)
+            .finished()
+    }));
+
+    /*
+     * Trailing bytes at the exact byte boundary
+     *
+     * `comp unit unit` with its shared `unit` and the empty witness block is exactly 16 bits:
+     *
+     * [program length 2] [unit] [comp 1 1] [no witness]
+     *  100                01001  00000 0 0  0
+     *
+     * A trailing zero byte after it is 8 unused bits, which are unused bytes.
+     *
+     * `unit` with the empty witness block is 7 bits, followed by 1 bit of legal padding.
+     * A trailing zero byte after it is 9 unused bits, which are also unused bytes,
+     * although the bits in the final program byte alone would be legal padding.
+     */
+    /// Return `comp unit unit`, which ends at a byte boundary, followed by `trailing_byte`.
+    fn byte_aligned_program(trailing_byte: Option<u8>) -> Vec<u8> {
+        let builder = BitBuilder::program_preamble(2)
+            .unit()
+            .comp(1, 1)
+            .assert_decodes_to(Cmr::comp(Cmr::unit(), Cmr::unit()))
+            .witness_preamble(0)
+            .assert_n_total_written(16);
+        match trailing_byte {
+            Some(byte) => builder.bits_be(u64::from(byte), 8).parser_stops_here(),
+            None => builder.program_finished(),
+        }
+    }
+
+    test_cases.push(Box::new(|| {
+        TestBuilder::comment("bitstream_trailing_bytes/byte_aligned_no_trailing_byte")
+            .description("Program ends at the byte boundary, without trailing bytes")
+            .raw_program(byte_aligned_program(None))
+            .raw_cmr(Cmr::comp(Cmr::unit(), Cmr::unit()))
+            .expected_error(ScriptError::Ok)
+            .finished()
+    }));
+
+    test_cases.push(Box::new(|| {
+        TestBuilder::comment("bitstream_trailing_bytes/byte_aligned_trailing_byte")
+            .description("Program ends at the byte boundary, followed by a trailing zero byte")
+            .raw_program(byte_aligned_program(Some(0x00)))
+            .raw_cmr(Cmr::comp(Cmr::unit(), Cmr::unit()))
+            .expected_error(ScriptError::SimplicityBitstreamUnusedBytes)
+            .finished()
+    }));
+
+    test_cases.push(Box::new(|| {
+        let bytes = BitBuilder::program_preamble(1)
+            .unit()
+            .witness_preamble(0)
+            .illegal_padding()
+            .bits_be(0b0, 1) // legal padding
+            .assert_n_total_written(8)
+            .bits_be(0x00, 8) // trailing byte
+            .parser_stops_here();
+        TestBuilder::comment("bitstream_trailing_bytes/legal_padding_and_trailing_byte")
+            .description("Legal padding in final program byte, followed by a trailing zero byte")
+            .raw_program(bytes)
+            .raw_cmr(Cmr::unit())
+            .expected_error(ScriptError::SimplicityBitstreamUnusedBytes)
+            .finished()
+    }));
+
+    super::generate(test_cases)
+}
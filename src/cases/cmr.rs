@@ -0,0 +1,49 @@
+//! # CMR
+//!
+//! Programs whose CMR does or does not match the script.
+
+use std::collections::HashMap;
+
+use simplicity::Cmr;
+
+use super::Deferred;
+use crate::json::{ScriptError, TestCase};
+use crate::test::TestBuilder;
+
+/// Return the default test cases of this category.
+pub fn cases() -> impl Iterator<Item = TestCase> {
+    let mut test_cases: Vec<Deferred> = Vec::new();
+
+    /*
+     * CMR mismatch inside Taproot witness
+     */
+    test_cases.push(Box::new(|| {
+        let s = "
+            main := unit
+        ";
+        let wrong_cmr = Cmr::iden();
+        TestBuilder::comment("cmr/mismatch")
+            .description("CMR mismatch inside Taproot witness")
+            .human_encoding(s, &HashMap::new())
+            .raw_cmr(wrong_cmr)
+            .allow_cmr_mismatch()
+            .expected_error(ScriptError::SimplicityCmr)
+            .finished()
+    }));
+
+    /*
+     * CMR match inside Taproot witness
+     */
+    test_cases.push(Box::new(|| {
+        let s = "
+            main := unit
+        ";
+        TestBuilder::comment("cmr/match")
+            .description("CMR match inside Taproot witness")
+            .human_encoding(s, &HashMap::new())
+            .expected_error(ScriptError::Ok)
+            .finished()
+    }));
+
+    super::generate(test_cases)
+}
@@ -0,0 +1,530 @@
+//! # Combinators
+//!
+//! Programs that exercise the combinators, witness nodes, constant words and jets when used as intended.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use elements_miniscript as miniscript;
+use miniscript::elements;
+use miniscript::elements::hashes::Hash as _;
+use simplicity::jet::{Elements, Jet};
+use simplicity::node::{CoreConstructible, JetConstructible, WitnessConstructible};
+use simplicity::{BitMachine, Cmr, Value, WitnessNode};
+
+use super::Deferred;
+use crate::bit_encoding::BitBuilder;
+use crate::json::{ScriptError, TestCase};
+use crate::test::TestBuilder;
+use crate::util::{self, Case};
+use crate::witness::WitnessBuilder;
+
+type Node = Arc<WitnessNode<Elements>>;
+
+/// Return the default test cases of this category.
+pub fn cases() -> impl Iterator<Item = TestCase> {
+    let mut test_cases: Vec<Deferred> = Vec::new();
+
+    /*
+     * `unit` is an ANYONECANSPEND
+     */
+    test_cases.push(Box::new(|| {
+        let s = "main := unit";
+        TestBuilder::comment("ok/unit")
+            .description("`unit` is an ANYONECANSPEND")
+            .human_encoding(s, &HashMap::new())
+            .expected_error(ScriptError::Ok)
+            .expected_final_stack(vec![])
+            .finished()
+    }));
+
+    /*
+     * `iden` is an ANYONECANSPEND
+     */
+    test_cases.push(Box::new(|| {
+        let s = "main := iden";
+        TestBuilder::comment("ok/iden")
+            .description("`iden` is an ANYONECANSPEND")
+            .human_encoding(s, &HashMap::new())
+            .expected_error(ScriptError::Ok)
+            .finished()
+    }));
+
+    /*
+     * Sum introduction and elimination
+     *
+     * injl unit:            1           → 1 + 1
+     * injr unit:            1           → 1 + 1
+     * assertl unit #{unit}: (1 + 1) × 1 → 1
+     * assertr #{unit} unit: (1 + 1) × 1 → 1
+     *
+     * The left injection is eliminated by the left assertion and vice versa,
+     * so the executed branch is never hidden and both programs succeed.
+     */
+    for (case, inj) in [(Case::Left, "injl"), (Case::Right, "injr")] {
+        test_cases.push(Box::new(move || {
+            let s = format!(
+                "
+                input := pair ({inj} unit) unit
+                main := comp input ({} {} {})
+            ",
+                case,
+                case.left_child("unit"),
+                case.right_child("unit")
+            );
+            TestBuilder::comment(format!("ok/{inj}"))
+                .description("Sum introduction and elimination")
+                .human_encoding(&s, &HashMap::new())
+                .expected_error(ScriptError::Ok)
+                .finished()
+        }));
+    }
+
+    /*
+     * Identity on a product of words
+     *
+     * input:      1           → 2^16 × 2^16
+     * iden:       2^16 × 2^16 → 2^16 × 2^16
+     * expected:   2^16 × 2^16 → 2^32
+     * jet_eq_32:  2^32 × 2^32 → 2
+     *
+     * The input is the pair (0xcafe, 0xbeef). `iden` passes it through unchanged,
+     * so it equals the constant 0xcafebeef (2^32 = 2^16 × 2^16).
+     */
+    test_cases.push(Box::new(|| {
+        let s = "
+            input := pair (const 0xcafe) (const 0xbeef)
+            expected := comp unit (const 0xcafebeef)
+            is_expected := comp (pair iden expected) jet_eq_32
+            main := comp input (comp is_expected jet_verify)
+        ";
+        TestBuilder::comment("ok/iden_product")
+            .description("Identity on a product of words")
+            .human_encoding(s, &HashMap::new())
+            .expected_error(ScriptError::Ok)
+            .finished()
+    }));
+
+    /*
+     * Projections from a product of words
+     *
+     * wit:       1          → 2^8 × 2^16
+     * take iden: 2^8 × 2^16 → 2^8
+     * drop iden: 2^8 × 2^16 → 2^16
+     *
+     * The witness value is (0xca, 0xbeef).
+     * `take` projects 0xca and `drop` projects 0xbeef.
+     * Each program compares one projection with the expected constant.
+     *
+     * A component that is never used would be inferred as unit,
+     * so the other component is projected into a complement jet that fixes its type.
+     */
+    /// Witness value (0xca, 0xbeef).
+    fn product_witness() -> HashMap<Arc<str>, Arc<Value>> {
+        WitnessBuilder::new()
+            .value("wit", Value::prod(Value::u8(0xca), Value::u16(0xbeef)))
+            .to_map()
+    }
+
+    for (projection, other, expected, eq, complement) in [
+        ("take", "drop", "0xca", "jet_eq_8", "jet_complement_16"),
+        ("drop", "take", "0xbeef", "jet_eq_16", "jet_complement_8"),
+    ] {
+        test_cases.push(Box::new(move || {
+            let s = format!(
+                "
+                wit := witness
+                projected := {projection} iden
+                other := {other} {complement}
+                expected := comp unit (const {expected})
+                is_expected := comp (comp (pair projected expected) {eq}) jet_verify
+                main := comp wit (comp (pair is_expected other) unit)
+            "
+            );
+            TestBuilder::comment(format!("ok/{projection}_witness_product"))
+                .description("Projections from a product of words")
+                .human_encoding(&s, &product_witness())
+                .expected_error(ScriptError::Ok)
+                .finished()
+        }));
+    }
+
+    /*
+     * `drop` projects the second component, not the first
+     *
+     * The witness value is (0xca, 0xbeef), as above.
+     * `drop iden` projects 0xbeef, so comparing it with the first component 0x00ca
+     * makes `jet_verify` fail. The passing comparison is `ok/drop_witness_product`.
+     */
+    test_cases.push(Box::new(|| {
+        let s = "
+            wit := witness
+            first := take jet_complement_8
+            second := drop iden
+            expected := comp unit (const 0x00ca)
+            is_expected := comp (comp (pair second expected) jet_eq_16) jet_verify
+            main := comp wit (comp (pair is_expected first) unit)
+        ";
+        TestBuilder::comment("exec_jet/drop_first_component")
+            .description("`drop` projects the second component, not the first")
+            .human_encoding(s, &product_witness())
+            .expected_error(ScriptError::SimplicityExecJet)
+            .finished()
+    }));
+
+    /*
+     * Disconnected continuation reads a witness
+     *
+     * pair unit unit:   2^256 × 1 → 1 × 1
+     * cont:             1         → 2
+     * dis:              1         → 1 × 2
+     * drop jet_verify:  1 × 2     → 1
+     *
+     * The witness value is 0xca.
+     * The continuation `cont` compares the witness with the constant 0xca
+     * and its output bit is passed through `dis` to `jet_verify`.
+     * The continuation is not part of the CMR, so it is free to read a witness.
+     */
+    test_cases.push(Box::new(|| {
+        let s = "
+            wit := witness
+            expected := comp unit (const 0xca)
+            cont := comp (pair wit expected) jet_eq_8
+            dis := disconnect (pair unit unit) ?cont
+            main := comp dis (drop jet_verify)
+        ";
+        let witness = WitnessBuilder::new().value("wit", Value::u8(0xca)).to_map();
+        let program = util::program_from_string_disconnect(s, &witness, &[("dis", "cont")]);
+        TestBuilder::comment("ok/disconnect_witness_continuation")
+            .description("Disconnected continuation reads a witness")
+            .program(&program)
+            .expected_error(ScriptError::Ok)
+            .finished()
+    }));
+
+    /*
+     * Left child of disconnect reads the 256-bit CMR of the continuation
+     *
+     * is_cont:             2^256 × 1 → 1
+     * pair is_cont unit:   2^256 × 1 → 1 × 1
+     * cont:                1         → 1
+     * dis:                 1         → 1 × 1
+     *
+     * `disconnect` passes the CMR of its right child to its left child.
+     * The left child `is_cont` compares this 256-bit value with the CMR of `cont`,
+     * which is committed as a 32-byte constant, so only this continuation is accepted.
+     * The continuation `cont` checks the bit 1 and runs to completion.
+     */
+    test_cases.push(Box::new(|| {
+        let cont = "comp (comp unit (const 0b1)) jet_verify";
+        let cont_cmr = util::program_from_string(&format!("main := {cont}"), &HashMap::new()).cmr();
+        let s = format!(
+            "
+            expected := comp unit (const 0x{cont_cmr})
+            is_cont := comp (comp (pair (take iden) expected) jet_eq_256) jet_verify
+            cont := {cont}
+            dis := disconnect (pair is_cont unit) ?cont
+            main := comp dis unit
+        "
+        );
+        let program = util::program_from_string_disconnect(&s, &HashMap::new(), &[("dis", "cont")]);
+        TestBuilder::comment("ok/disconnect_left_child_reads_cmr")
+            .description("Left child of disconnect reads the 256-bit CMR of the continuation")
+            .program(&program)
+            .expected_error(ScriptError::Ok)
+            .finished()
+    }));
+
+    /*
+     * Witness value has complex type of zero bit size (DDos)
+     *
+     * Witness node with target type that is exponential product of unit
+     */
+    test_cases.push(Box::new(|| {
+        let s = "
+            unpack0 := iden : 1 -> 1
+            unpack1 := comp (pair (take unpack0) (drop unpack0)) unit : 1 * 1 -> 1
+            unpack2 := comp (pair (take unpack1) (drop unpack1)) unit : (1 * 1) * (1 * 1) -> 1
+            unpack3 := comp (pair (take unpack2) (drop unpack2)) unit
+            unpack4 := comp (pair (take unpack3) (drop unpack3)) unit
+            unpack5 := comp (pair (take unpack4) (drop unpack4)) unit
+            unpack6 := comp (pair (take unpack5) (drop unpack5)) unit
+            unpack7 := comp (pair (take unpack6) (drop unpack6)) unit
+            unpack8 := comp (pair (take unpack7) (drop unpack7)) unit
+            unpack9 := comp (pair (take unpack8) (drop unpack8)) unit
+            unpack10 := comp (pair (take unpack9) (drop unpack9)) unit
+            unpack11 := comp (pair (take unpack10) (drop unpack10)) unit
+            unpack12 := comp (pair (take unpack11) (drop unpack11)) unit
+            unpack13 := comp (pair (take unpack12) (drop unpack12)) unit
+            unpack14 := comp (pair (take unpack13) (drop unpack13)) unit
+            unpack15 := comp (pair (take unpack14) (drop unpack14)) unit
+            wit := witness
+            main := comp wit unpack15
+        ";
+        let mut value = Value::unit();
+        for _ in 0..15 {
+            value = Value::prod(value.clone(), value);
+        }
+        let witness = WitnessBuilder::new().value("wit", value).to_map();
+        TestBuilder::comment("ok/complex_witness_type_zero_size")
+            .description("Witness value has complex type of zero bit size (DDos)")
+            .human_encoding(s, &witness)
+            .expected_error(ScriptError::Ok)
+            .finished()
+    }));
+
+    /*
+     * Two witness nodes of the same type hold distinct values
+     *
+     * wit1: 1 → 2^8 with value 0x01
+     * wit2: 1 → 2^8 with value 0x02
+     *
+     * Both witness nodes have the same CMR, but their IMRs differ because their values differ.
+     * The nodes are unshared and the witness block contains both values, in order.
+     */
+    /// Return the program with two witness nodes and its witness values 0x01 and 0x02.
+    fn two_witness_nodes() -> (&'static str, HashMap<Arc<str>, Arc<Value>>) {
+        let s = "
+            wit1 := witness
+            wit2 := witness
+            check1 := comp (comp (pair wit1 (const 0x01)) jet_eq_8) jet_verify
+            check2 := comp (comp (pair wit2 (const 0x02)) jet_eq_8) jet_verify
+            main := comp (pair check1 check2) unit
+        ";
+        let witness = WitnessBuilder::new()
+            .value("wit1", Value::u8(0x01))
+            .value("wit2", Value::u8(0x02))
+            .to_map();
+        (s, witness)
+    }
+
+    test_cases.push(Box::new(|| {
+        let (s, witness) = two_witness_nodes();
+        TestBuilder::comment("ok/two_witness_nodes")
+            .description("Two witness nodes of the same type hold distinct values")
+            .human_encoding(s, &witness)
+            .expected_error(ScriptError::Ok)
+            .finished()
+    }));
+
+    /*
+     * One shared witness node is used where two distinct witnesses are intended
+     *
+     * Same program as above, but both uses refer to a single witness node with value 0x01.
+     * The CMR is the same, because it does not commit to witness nodes or their sharing.
+     * The witness block contains a single value, so both uses see 0x01
+     * and the check for 0x02 fails.
+     *
+     * The human encoding rejects a witness name that is used twice,
+     * so the program is built from nodes.
+     */
+    test_cases.push(Box::new(|| {
+        let wit = Node::witness(Some(Value::u8(0x01)));
+        let check = |expected: u8| {
+            let pair = Node::pair(&wit, &Node::const_word(Value::u8(expected))).unwrap();
+            let eq = Node::comp(&pair, &Node::jet(Elements::Eq8)).unwrap();
+            Node::comp(&eq, &Node::jet(Elements::Verify)).unwrap()
+        };
+        let checks = Node::pair(&check(0x01), &check(0x02)).unwrap();
+        let program = Node::comp(&checks, &Node::unit())
+            .unwrap()
+            .finalize()
+            .expect("well-typed and sufficient witness");
+        let (s, witness) = two_witness_nodes();
+        assert_eq!(util::program_from_string(s, &witness).cmr(), program.cmr());
+        TestBuilder::comment("exec_jet/shared_witness_node")
+            .description(
+                "One shared witness node is used where two distinct witnesses are intended",
+            )
+            .program(&program)
+            .expected_error(ScriptError::SimplicityExecJet)
+            .finished()
+    }));
+
+    /*
+     * Constant words of each standard width
+     *
+     * word:       1   → 2^n
+     * unit:       2^n → 1
+     * comp (..):  1   → 1
+     *
+     * Widths n = 1, 2, 4, 8, 16, 32, 64, 128, 256.
+     * A word of width 2^(depth - 1) is encoded with the given depth.
+     */
+    /// Program that computes the constant `value` and discards it.
+    fn const_word_program(value: &Value) -> (Vec<u8>, Cmr) {
+        let depth = value.len().trailing_zeros() as usize + 1;
+        let bytes = BitBuilder::program_preamble(3)
+            .word(depth, value)
+            .unit()
+            .comp(2, 1)
+            .witness_preamble(0)
+            .program_finished();
+        let cmr = Cmr::comp(Cmr::const_word(value), Cmr::unit());
+        (bytes, cmr)
+    }
+
+    for value in [
+        Value::u1(0b1),
+        Value::u2(0b10),
+        Value::u4(0xa),
+        Value::u8(0xca),
+        Value::u16(0xbeef),
+        Value::u32(0xdeadbeef),
+        Value::u64(0x0123_4567_89ab_cdef),
+        Value::u128(0x0123_4567_89ab_cdef_fedc_ba98_7654_3210),
+        Value::u256_from_slice(&[0xa5; 32]),
+    ] {
+        test_cases.push(Box::new(move || {
+            assert!(value.len().is_power_of_two());
+            TestBuilder::comment(format!("ok/const_word_{}", value.len()))
+                .description("Constant words of each standard width")
+                .raw_program_cmr(const_word_program(&value))
+                .expected_error(ScriptError::Ok)
+                .finished()
+        }));
+    }
+
+    /*
+     * Many distinct jets in one program
+     *
+     * out_i := comp zero_i jet_i                                : 1 → B_i
+     * main  := comp (pair out_0 (pair out_1 (.. unit))) unit    : 1 → 1
+     *
+     * Each jet is fed the all-zero value of its source type, and its output is discarded.
+     * This includes every Elements jet that succeeds on zero, which is checked by running it.
+     * The program has a few kilobytes, so it stays in the default set.
+     *
+     * The program is built from nodes, because the human encoding is too slow to parse it.
+     */
+    /// Return the program that runs `jet` on zero and discards its output.
+    fn zero_jet_program(jet: Elements) -> Node {
+        Node::comp(&util::zero_node(&jet.source_ty()), &Node::jet(jet)).expect("source type of jet")
+    }
+
+    /// Return whether `jet` succeeds on the all-zero value of its source type.
+    fn succeeds_on_zero(jet: Elements) -> bool {
+        let program = Node::comp(&zero_jet_program(jet), &Node::unit())
+            .unwrap()
+            .finalize()
+            .expect("well-typed and sufficient witness");
+        let (tx, prevouts, index) = util::minimal_spend_for_cmr(program.cmr());
+        let spend_info = util::get_spend_info(program.cmr(), simplicity::leaf_version());
+        let control_block =
+            util::get_control_block(program.cmr(), simplicity::leaf_version(), &spend_info)
+                .expect("leaf exists")
+                .serialize();
+        let env = util::elements_env(
+            Arc::new(tx),
+            &prevouts,
+            index,
+            program.cmr(),
+            &control_block,
+            elements::BlockHash::all_zeros(),
+        );
+        BitMachine::for_program(&program)
+            .exec(&program, &env)
+            .is_ok()
+    }
+
+    test_cases.push(Box::new(|| {
+        let jets: Vec<Elements> = util::elements_jets()
+            .into_iter()
+            .filter(|jet| succeeds_on_zero(*jet))
+            .collect();
+        let mut all = Node::unit();
+        for jet in jets.iter().rev() {
+            all = Node::pair(&zero_jet_program(*jet), &all).expect("source types are unit");
+        }
+        let program = Node::comp(&all, &Node::unit())
+            .unwrap()
+            .finalize()
+            .expect("well-typed and sufficient witness");
+        TestBuilder::comment("ok/distinct_jets")
+            .description("Many distinct jets in one program")
+            .program(&program)
+            .expected_error(ScriptError::Ok)
+            .finished()
+    }));
+
+    /*
+     * `comp` nested to the right at great depth
+     *
+     * The `cpN` ladders in `exec_budget` are nested to the left (`comp cp cp`) and shared,
+     * so they are shallow DAGs. This program is a deep chain instead,
+     * which stresses validators that keep a stack of frames while executing.
+     */
+    /// Return a program of the given `depth` that is typed `1 → 1` and always succeeds.
+    ///
+    /// Depth 1 is `comp iden iden`.
+    /// Depth `d + 1` is `comp iden p`, where `p` is the program of depth `d`.
+    ///
+    /// All `iden` nodes are shared, so the program has `depth + 1` nodes.
+    /// The shared `iden` comes first, and each `comp` follows its right child,
+    /// which is the canonical order.
+    fn right_nested_comp_program(depth: usize) -> (Vec<u8>, Cmr) {
+        assert!(0 < depth, "Depth must be positive");
+        let mut builder = BitBuilder::program_preamble(depth + 1).iden();
+        let mut cmr = Cmr::iden();
+
+        for index in 1..=depth {
+            builder = builder.comp(index, 1);
+            cmr = Cmr::comp(Cmr::iden(), cmr);
+        }
+
+        let bytes = builder.witness_preamble(0).program_finished();
+        (bytes, cmr)
+    }
+
+    test_cases.push(Box::new(|| {
+        let depth = 10_000;
+        TestBuilder::comment(format!("ok/right_nested_comp_depth_{depth}"))
+            .description("`comp` nested to the right at great depth")
+            .raw_program_cmr(right_nested_comp_program(depth))
+            .large_stack()
+            .expected_error(ScriptError::Ok)
+            .finished()
+    }));
+
+    super::generate(test_cases)
+}
+
+/// Return the smoke tests of nested `comp unit unit`.
+pub fn smoke_cases() -> impl Iterator<Item = TestCase> {
+    let mut test_cases: Vec<Deferred> = Vec::new();
+
+    /*
+     * Smoke test: `comp unit unit` nested to some depth
+     */
+    /// Return a program of the given `depth` that is typed `1 → 1` and always succeeds.
+    ///
+    /// Depth 1 is `comp unit unit`.
+    /// Depth `d + 1` is `comp p unit`, where `p` is the program of depth `d`.
+    ///
+    /// All `unit` nodes are shared, so the program has `depth + 1` nodes.
+    fn comp_unit_program(depth: usize) -> (Vec<u8>, Cmr) {
+        assert!(0 < depth, "Depth must be positive");
+        let mut builder = BitBuilder::program_preamble(depth + 1).unit();
+        let mut cmr = Cmr::unit();
+
+        for index in 1..=depth {
+            builder = builder.comp(1, index);
+            cmr = Cmr::comp(cmr, Cmr::unit());
+        }
+
+        let bytes = builder.witness_preamble(0).program_finished();
+        (bytes, cmr)
+    }
+
+    for depth in [1, 10, 100] {
+        test_cases.push(Box::new(move || {
+            TestBuilder::comment(format!("ok/comp_unit_depth_{depth}"))
+                .description("Smoke test: `comp unit unit` nested to some depth")
+                .raw_program_cmr(comp_unit_program(depth))
+                .expected_error(ScriptError::Ok)
+                .finished()
+        }));
+    }
+
+    super::generate(test_cases)
+}
@@ -0,0 +1,120 @@
+//! # Data out of order
+//!
+//! Programs that are not serialized in canonical order.
+
+use simplicity::{Cmr, Value};
+
+use super::Deferred;
+use crate::bit_encoding::BitBuilder;
+use crate::json::{ScriptError, TestCase};
+use crate::test::TestBuilder;
+use crate::util;
+
+/// Return the default test cases of this category.
+pub fn cases() -> impl Iterator<Item = TestCase> {
+    let mut test_cases: Vec<Deferred> = Vec::new();
+
+    /*
+     * Program is not serialized in canonical order
+     */
+    /// Program that causes SIMPLICITY_DATA_OUT_OF_ORDER iff canonical is false
+    fn canonical_order_program(canonical: bool) -> (Vec<u8>, Cmr) {
+        let (left_offset, right_offset) = match canonical {
+            false => (1, 2),
+            true => (2, 1),
+        };
+        let bytes = BitBuilder::program_preamble(3)
+            .unit()
+            .iden()
+            .comp(left_offset, right_offset)
+            .witness_preamble(0)
+            .program_finished();
+        let cmr = Cmr::comp(Cmr::unit(), Cmr::iden());
+        (bytes, cmr)
+    }
+
+    test_cases.push(Box::new(|| {
+        util::assert_same_cmr_different_bytes(
+            &canonical_order_program(false),
+            &canonical_order_program(true),
+        );
+        TestBuilder::comment("data_out_of_order/not_in_canonical_order")
+            .description("Program is not serialized in canonical order")
+            .raw_program_cmr(canonical_order_program(false))
+            .expected_error(ScriptError::SimplicityDataOutOfOrder)
+            .finished()
+    }));
+
+    /*
+     * Program is serialized in canonical order
+     */
+    test_cases.push(Box::new(|| {
+        TestBuilder::comment("data_out_of_order/in_canonical_order")
+            .description("Program is serialized in canonical order")
+            .raw_program_cmr(canonical_order_program(true))
+            .expected_error(ScriptError::Ok)
+            .finished()
+    }));
+
+    /*
+     * Hidden node is not serialized in canonical order
+     *
+     * Hidden nodes are leaves like any other: the left child of `case` must come first,
+     * even if it is hidden and even if the right child is a larger subexpression.
+     * In the canonical order, the hidden left child precedes `take unit`.
+     * Swapping both nodes puts the right branch of `case` in front of the left branch.
+     */
+    /// Program that causes SIMPLICITY_DATA_OUT_OF_ORDER iff canonical is false
+    fn hidden_order_program(canonical: bool) -> (Vec<u8>, Cmr) {
+        let value = Value::u1(1);
+        let hidden = Cmr::take(Cmr::unit());
+        let builder = BitBuilder::program_preamble(7)
+            .word(1, &value) // 1 → 2
+            .unit() // 1 → 1
+            .pair(2, 1); // 1 → 2 × 1
+        let builder = match canonical {
+            true => builder
+                .hidden(hidden)
+                .take(3) // 1 × 1 → 1
+                .case(2, 1), // (1 + 1) × 1 → 1
+            false => builder
+                .take(2) // 1 × 1 → 1
+                .hidden(hidden)
+                .case(1, 2), // (1 + 1) × 1 → 1
+        };
+        let bytes = builder
+            .comp(4, 1) // 1 → 1
+            .witness_preamble(0)
+            .program_finished();
+        let cmr = Cmr::comp(
+            Cmr::pair(Cmr::const_word(&value), Cmr::unit()),
+            Cmr::case(hidden, Cmr::take(Cmr::unit())),
+        );
+        (bytes, cmr)
+    }
+
+    test_cases.push(Box::new(|| {
+        util::assert_same_cmr_different_bytes(
+            &hidden_order_program(false),
+            &hidden_order_program(true),
+        );
+        TestBuilder::comment("data_out_of_order/hidden_not_in_canonical_order")
+            .description("Hidden node is not serialized in canonical order")
+            .raw_program_cmr(hidden_order_program(false))
+            .expected_error(ScriptError::SimplicityDataOutOfOrder)
+            .finished()
+    }));
+
+    /*
+     * Hidden node is serialized in canonical order
+     */
+    test_cases.push(Box::new(|| {
+        TestBuilder::comment("data_out_of_order/hidden_in_canonical_order")
+            .description("Hidden node is serialized in canonical order")
+            .raw_program_cmr(hidden_order_program(true))
+            .expected_error(ScriptError::Ok)
+            .finished()
+    }));
+
+    super::generate(test_cases)
+}
@@ -0,0 +1,284 @@
+//! # Data out of range
+//!
+//! Programs whose encoding contains a number or a code that exceeds its range.
+
+use simplicity::{Cmr, Value};
+
+use super::Deferred;
+use crate::bit_encoding::BitBuilder;
+use crate::json::{ScriptError, TestCase};
+use crate::test::TestBuilder;
+
+/// Return the default test cases of this category.
+pub fn cases() -> impl Iterator<Item = TestCase> {
+    let mut test_cases: Vec<Deferred> = Vec::new();
+
+    /*
+     * DAG_LEN_MAX < program length
+     */
+    /// If exceeds is true, then program causes SIMPLICITY_DATA_OUT_OF_RANGE
+    ///
+    /// If exceeds is false, then program causes SIMPLICITY_BITSTREAM_EOF
+    // Too lazy to write a program of DAG_LEN_MAX many nodes
+    // Instead, test that parser goes past program length and runs out of bits to read
+    fn program_length_max_program(exceeds_max: bool) -> (Vec<u8>, Cmr) {
+        let dag_len_max = 8_000_000;
+        let bytes = BitBuilder::program_preamble(dag_len_max + usize::from(exceeds_max))
+            .bits_be(u64::MAX, 6)
+            .assert_n_total_written(5 * 8)
+            .parser_stops_here();
+        let cmr = Cmr::from_byte_array([0; 32]);
+
+        (bytes, cmr)
+    }
+
+    test_cases.push(Box::new(|| {
+        TestBuilder::comment("data_out_of_range/program_length_exceeds_max")
+            .description("DAG_LEN_MAX < program length")
+            .raw_program_cmr(program_length_max_program(true))
+            .expected_error(ScriptError::SimplicityDataOutOfRange)
+            .finished()
+    }));
+
+    /*
+     * program length <= DAG_LEN_MAX
+     */
+    test_cases.push(Box::new(|| {
+        TestBuilder::comment("data_out_of_range/program_length_ok")
+            .description("program length <= DAG_LEN_MAX")
+            .raw_program_cmr(program_length_max_program(false))
+            .expected_error(ScriptError::SimplicityBitstreamEof)
+            .finished()
+    }));
+
+    /*
+     * 2^31 <= witness length
+     */
+    /// If 2^31 <= bit_len, then program causes SIMPLICITY_DATA_OUT_OF_RANGE
+    ///
+    /// If bit_len < 2^31, then program causes SIMPLICITY_BITSTREAM_EOF
+    // Too lazy to write 2^31 - 1 many bits = 2 GiB!
+    // Instead, test that parser goes past witness length and runs out of bits to read
+    fn witness_length_program(bit_len: usize) -> (Vec<u8>, Cmr) {
+        let bytes = BitBuilder::program_preamble(3)
+            .witness()
+            .unit()
+            .comp(2, 1)
+            .witness_preamble(bit_len)
+            .parser_stops_here();
+        let cmr = Cmr::comp(Cmr::witness(), Cmr::unit());
+        (bytes, cmr)
+    }
+
+    test_cases.push(Box::new(|| {
+        TestBuilder::comment("data_out_of_range/witness_length_exceeds_max")
+            .description("2^31 <= witness length")
+            .raw_program_cmr(witness_length_program(1 << 31))
+            .expected_error(ScriptError::SimplicityDataOutOfRange)
+            .finished()
+    }));
+
+    /*
+     * witness length < 2^31
+     */
+    test_cases.push(Box::new(|| {
+        TestBuilder::comment("data_out_of_range/witness_length_ok")
+            .description("witness length < 2^31")
+            .raw_program_cmr(witness_length_program((1 << 31) - 1))
+            .expected_error(ScriptError::SimplicityBitstreamEof)
+            .finished()
+    }));
+
+    /*
+     * Relative child index points past beginning of program
+     */
+    /// Program causes SIMPLICITY_DATA_OUT_OF_RANGE iff 1 < left_offset
+    fn combinator_child_index_program(left_offset: usize) -> (Vec<u8>, Cmr) {
+        let bytes = BitBuilder::program_preamble(2)
+            .unit()
+            .comp(left_offset, 1)
+            .witness_preamble(0)
+            .program_finished();
+        let cmr = Cmr::comp(Cmr::unit(), Cmr::unit());
+        (bytes, cmr)
+    }
+
+    test_cases.push(Box::new(|| {
+        TestBuilder::comment("data_out_of_range/relative_child_index_too_large")
+            .description("Relative child index points past beginning of program")
+            .raw_program_cmr(combinator_child_index_program(2))
+            .expected_error(ScriptError::SimplicityDataOutOfRange)
+            .finished()
+    }));
+
+    /*
+     * Relative child index points inside program
+     */
+    test_cases.push(Box::new(|| {
+        TestBuilder::comment("data_out_of_range/relative_child_index_ok")
+            .description("Relative child index points inside program")
+            .raw_program_cmr(combinator_child_index_program(1))
+            .expected_error(ScriptError::Ok)
+            .finished()
+    }));
+
+    /*
+     * Relative child index points to the first node of a larger program
+     */
+    /// Program of 10 nodes whose root references the first node
+    ///
+    /// unit (node 0) and comp^7 iden (nodes 1 to 8) are composed at the root (node 9).
+    /// The left child index of the root is 9, which is the largest valid offset.
+    fn max_child_index_program() -> (Vec<u8>, Cmr) {
+        let mut builder = BitBuilder::program_preamble(10).unit().iden();
+        let mut right_cmr = Cmr::iden();
+        for _ in 0..7 {
+            builder = builder.comp(1, 1);
+            right_cmr = Cmr::comp(right_cmr, right_cmr);
+        }
+        let bytes = builder.comp(9, 1).witness_preamble(0).program_finished();
+        let cmr = Cmr::comp(Cmr::unit(), right_cmr);
+
+        (bytes, cmr)
+    }
+
+    test_cases.push(Box::new(|| {
+        TestBuilder::comment("data_out_of_range/relative_child_index_max_ok")
+            .description("Relative child index points to the first node of a larger program")
+            .raw_program_cmr(max_child_index_program())
+            .expected_error(ScriptError::Ok)
+            .finished()
+    }));
+
+    /*
+     * Jet is not defined
+     */
+    test_cases.push(Box::new(|| {
+        let bytes = BitBuilder::program_preamble(1)
+            .jet(u64::MAX, 64) // It is unlikely that all-ones will become a jet soon
+            .witness_preamble(0)
+            .program_finished();
+        TestBuilder::comment("data_out_of_range/undefined_jet")
+            .description("Jet is not defined")
+            .raw_program(bytes)
+            .raw_cmr([0; 32])
+            .expected_error(ScriptError::SimplicityDataOutOfRange)
+            .finished()
+    }));
+
+    /*
+     * 32 < word depth (2^31 bits < word length)
+     */
+    /// If 32 < depth, then program causes SIMPLICITY_DATA_OUT_OF_RANGE
+    ///
+    /// If depth <= 32, then program causes SIMPLICITY_BITSTREAM_EOF
+    // Too lazy to write 2^31 many bits = 2 GiB!
+    // Instead, test that parser goes past word depth and runs out of bits to read
+    fn word_depth_program(depth: usize) -> (Vec<u8>, Cmr) {
+        let value = Value::u1(0);
+        let bytes = BitBuilder::program_preamble(1)
+            .word(depth, &value)
+            .parser_stops_here();
+        let cmr = Cmr::from_byte_array([0; 32]);
+        (bytes, cmr)
+    }
+
+    test_cases.push(Box::new(|| {
+        TestBuilder::comment("data_out_of_range/word_depth_exceeds_max")
+            .description("32 < word depth (2^31 bits < word length)")
+            .raw_program_cmr(word_depth_program(33))
+            .expected_error(ScriptError::SimplicityDataOutOfRange)
+            .finished()
+    }));
+
+    /*
+     * word_depth <= 32
+     */
+    test_cases.push(Box::new(|| {
+        TestBuilder::comment("data_out_of_range/word_depth_ok")
+            .description("word_depth <= 32")
+            .raw_program_cmr(word_depth_program(32))
+            .expected_error(ScriptError::SimplicityBitstreamEof)
+            .finished()
+    }));
+
+    /*
+     * Node tag is an unassigned jet code
+     *
+     * Every 5-bit combinator tag is assigned, so the reserved encodings live in the jet code space.
+     * A jet tag is 0b11 followed by the jet code.
+     * The 8-bit jet code 0b00100100 is unassigned: It sits between `low_1` (0b001000)
+     * and `low_8` (0b00100101).
+     */
+    test_cases.push(Box::new(|| {
+        let bytes = BitBuilder::program_preamble(1)
+            .raw_node(0b11_00100100, 10)
+            .parser_stops_here();
+        TestBuilder::comment("data_out_of_range/unassigned_jet_code")
+            .description("Node tag is an unassigned jet code")
+            .raw_program(bytes)
+            .raw_cmr(Cmr::from_byte_array([0; 32]))
+            .expected_error(ScriptError::SimplicityDataOutOfRange)
+            .finished()
+    }));
+
+    super::generate(test_cases)
+}
+
+/// Return the test cases with programs of DAG_LEN_MAX many real nodes.
+pub fn dag_len_max_cases() -> impl Iterator<Item = TestCase> {
+    let mut test_cases: Vec<Deferred> = Vec::new();
+
+    /*
+     * Program length is DAG_LEN_MAX or more, with real nodes
+     *
+     * The program is a DAG of Fibonacci shape:
+     * Node 0 is `unit`.
+     * Node 1 is `comp 0 0`.
+     * Node k is `comp (k - 1) (k - 2)` for k >= 2.
+     *
+     * Every node is distinct and its children are at most two nodes away,
+     * so each node takes only 9 bits.
+     * A program of DAG_LEN_MAX = 8,000,000 nodes takes 9 MB.
+     *
+     * The validator accepts DAG_LEN_MAX many nodes. The program decodes and type checks,
+     * but its cost grows exponentially, so it runs out of budget.
+     * The validator rejects DAG_LEN_MAX + 1 many nodes.
+     */
+    /// Return a program of Fibonacci shape with `len` many nodes.
+    fn fibonacci_dag_program(len: usize) -> (Vec<u8>, Cmr) {
+        assert!(2 <= len, "Program must have at least two nodes");
+        let mut builder = BitBuilder::program_preamble(len).unit().comp(1, 1);
+        let mut cmrs = (Cmr::unit(), Cmr::comp(Cmr::unit(), Cmr::unit()));
+
+        for _ in 2..len {
+            builder = builder.comp(1, 2);
+            cmrs = (cmrs.1, Cmr::comp(cmrs.1, cmrs.0));
+        }
+
+        let bytes = builder.witness_preamble(0).program_finished();
+        (bytes, cmrs.1)
+    }
+
+    const DAG_LEN_MAX: usize = 8_000_000;
+
+    test_cases.push(Box::new(|| {
+        TestBuilder::comment("data_out_of_range/program_length_max_real_nodes")
+            .description("Program length is DAG_LEN_MAX or more, with real nodes")
+            .raw_program_cmr(fibonacci_dag_program(DAG_LEN_MAX))
+            // The library decodes at most 1,000,000 nodes
+            .cmr_check(false)
+            .expected_error(ScriptError::SimplicityExecBudget)
+            .finished()
+    }));
+
+    test_cases.push(Box::new(|| {
+        TestBuilder::comment("data_out_of_range/program_length_exceeds_max_real_nodes")
+            .description("Program length is DAG_LEN_MAX or more, with real nodes")
+            .raw_program_cmr(fibonacci_dag_program(DAG_LEN_MAX + 1))
+            .expected_error(ScriptError::SimplicityDataOutOfRange)
+            .finished()
+    }));
+
+    super::generate(test_cases)
+}
@@ -0,0 +1,279 @@
+//! # Execution budget
+//!
+//! Programs whose cost does or does not exceed the budget of the witness stack.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use simplicity::jet::Elements;
+use simplicity::node::{CoreConstructible, WitnessConstructible};
+use simplicity::{Cmr, RedeemNode, WitnessNode};
+
+use super::Deferred;
+use crate::json::{ScriptError, TestCase};
+use crate::test::TestBuilder;
+use crate::util;
+
+type Node = Arc<WitnessNode<Elements>>;
+
+/// Return the default test cases of this category.
+pub fn cases() -> impl Iterator<Item = TestCase> {
+    let mut test_cases: Vec<Deferred> = Vec::new();
+
+    /*
+     * Expensive program has insufficient padding
+     */
+    /// Ladder of compositions whose cost exceeds the budget of the unpadded witness stack
+    const EXPENSIVE_PROGRAM: &str = "
+        id0 := iden
+        cp0 := comp id0 id0
+        cp1 := comp cp0 cp0
+        cp2 := comp cp1 cp1
+        cp3 := comp cp2 cp2
+        cp4 := comp cp3 cp3
+        cp5 := comp cp4 cp4
+        cp6 := comp cp5 cp5
+        cp7 := comp cp6 cp6
+        cp8 := comp cp7 cp7
+        cp9 := comp cp8 cp8
+        main := comp cp9 cp9
+    ";
+
+    test_cases.push(Box::new(|| {
+        TestBuilder::comment("exec_budget/insufficient_padding")
+            .description("Expensive program has insufficient padding")
+            .human_encoding(EXPENSIVE_PROGRAM, &HashMap::new())
+            .reset_cost()
+            .expected_error(ScriptError::SimplicityExecBudget)
+            .finished()
+    }));
+
+    /*
+     * Expensive program has half of the required padding
+     *
+     * The annex has half as many zero bytes as the program cost requires.
+     */
+    test_cases.push(Box::new(|| {
+        TestBuilder::comment("exec_budget/half_padding")
+            .description("Expensive program has half of the required padding")
+            .human_encoding(EXPENSIVE_PROGRAM, &HashMap::new())
+            .padding_factor(0.5)
+            .expected_error(ScriptError::SimplicityExecBudget)
+            .finished()
+    }));
+
+    /*
+     * Smallest composition ladder that needs padding
+     *
+     * The ladder of depth d is `comp cp cp`, where `cp` is the ladder of depth d - 1,
+     * and the ladder of depth 0 is `comp iden iden`.
+     * The cost doubles with each level, while the witness stack grows by a few bytes.
+     * Depth 9 is the smallest depth whose cost exceeds the budget of the witness stack.
+     *
+     * The padded program succeeds and the unpadded program fails.
+     */
+    /// Return the composition ladder of the given `depth`.
+    fn comp_ladder_program(depth: usize) -> Arc<RedeemNode<Elements>> {
+        let mut program = Node::comp(&Node::iden(), &Node::iden()).unwrap();
+        for _ in 0..depth {
+            program = Node::comp(&program, &program).unwrap();
+        }
+        program
+            .finalize()
+            .expect("well-typed and sufficient witness")
+    }
+
+    /// Return the smallest depth of a composition ladder that needs padding.
+    fn smallest_padded_depth() -> usize {
+        let depth = (0..)
+            .find(|&depth| {
+                TestBuilder::comment("")
+                    .program(&comp_ladder_program(depth))
+                    .expected_error(ScriptError::Ok)
+                    .needs_padding()
+            })
+            .expect("cost grows without bound");
+        assert_eq!(9, depth, "Update the comment above");
+        depth
+    }
+
+    test_cases.push(Box::new(|| {
+        let depth = smallest_padded_depth();
+        TestBuilder::comment(format!("ok/comp_ladder_depth_{depth}_padded"))
+            .description("Smallest composition ladder that needs padding")
+            .program(&comp_ladder_program(depth))
+            .expected_error(ScriptError::Ok)
+            .finished()
+    }));
+
+    test_cases.push(Box::new(|| {
+        let depth = smallest_padded_depth();
+        TestBuilder::comment(format!("exec_budget/comp_ladder_depth_{depth}_unpadded"))
+            .description("Smallest composition ladder that needs padding")
+            .program(&comp_ladder_program(depth))
+            .reset_cost()
+            .expected_error(ScriptError::SimplicityExecBudget)
+            .finished()
+    }));
+
+    /*
+     * Annex that is not padding
+     *
+     * Simplicity ignores the annex, but the annex counts towards the budget.
+     * The cheap program needs no padding, so the annex is pushed as is.
+     * The ladder needs padding, so the annex is extended by zero bytes
+     * instead of pushing a second annex.
+     */
+    test_cases.push(Box::new(|| {
+        TestBuilder::comment("ok/unit_extra_annex")
+            .description("Annex that is not padding")
+            .human_encoding("main := unit", &HashMap::new())
+            .extra_annex(vec![0x50, 0xca, 0xfe, 0xba, 0xbe])
+            .expected_error(ScriptError::Ok)
+            .finished()
+    }));
+
+    test_cases.push(Box::new(|| {
+        let depth = smallest_padded_depth();
+        TestBuilder::comment(format!("ok/comp_ladder_depth_{depth}_extra_annex"))
+            .description("Annex that is not padding")
+            .program(&comp_ladder_program(depth))
+            .extra_annex(vec![0x50, 0xca, 0xfe, 0xba, 0xbe])
+            .expected_error(ScriptError::Ok)
+            .finished()
+    }));
+
+    /*
+     * Expensive program has sufficient padding, but costs more than MAX_BUDGET
+     */
+    test_cases.push(Box::new(|| {
+        let s = "
+            id0 := iden
+            cp0 := comp id0 id0
+            cp1 := comp cp0 cp0
+            cp2 := comp cp1 cp1
+            cp3 := comp cp2 cp2
+            cp4 := comp cp3 cp3
+            cp5 := comp cp4 cp4
+            cp6 := comp cp5 cp5
+            cp7 := comp cp6 cp6
+            cp8 := comp cp7 cp7
+            cp9 := comp cp8 cp8
+            cp10 := comp cp9 cp9
+            cp11 := comp cp10 cp10
+            cp12 := comp cp11 cp11
+            cp13 := comp cp12 cp12
+            cp14 := comp cp13 cp13
+            cp15 := comp cp14 cp14
+            cp16 := comp cp15 cp15
+            cp17 := comp cp16 cp16
+            cp18 := comp cp17 cp17
+            cp19 := comp cp18 cp18
+            cp20 := comp cp19 cp19
+            cp21 := comp cp20 cp20
+            cp22 := comp cp21 cp21
+            cp23 := comp cp22 cp22
+            main := comp cp23 cp23
+        ";
+        let program = util::program_from_string(s, &HashMap::new());
+        let (weight, _) = util::describe_cost(&program);
+        assert!(4_000_050 < weight, "Program must cost more than MAX_BUDGET");
+        TestBuilder::comment("exec_budget/padding_exceeds_max_budget")
+            .description("Expensive program has sufficient padding, but costs more than MAX_BUDGET")
+            .program(&program)
+            .expected_error(ScriptError::SimplicityExecBudget)
+            .finished()
+    }));
+
+    /*
+     * Expensive program has sufficient padding (C test vector)
+     */
+    /// Ladder of compositions whose cost is at most MAX_BUDGET
+    const PADDED_PROGRAM: &str = "
+        id0 := iden
+        cp0 := comp id0 id0
+        cp1 := comp cp0 cp0
+        cp2 := comp cp1 cp1
+        cp3 := comp cp2 cp2
+        cp4 := comp cp3 cp3
+        cp5 := comp cp4 cp4
+        cp6 := comp cp5 cp5
+        cp7 := comp cp6 cp6
+        cp8 := comp cp7 cp7
+        cp9 := comp cp8 cp8
+        cp10 := comp cp9 cp9
+        cp11 := comp cp10 cp10
+        cp12 := comp cp11 cp11
+        cp13 := comp cp12 cp12
+        cp14 := comp cp13 cp13
+        cp15 := comp cp14 cp14
+        cp16 := comp cp15 cp15
+        cp17 := comp cp16 cp16
+        cp18 := comp cp17 cp17
+        cp19 := comp cp18 cp18
+        cp20 := comp cp19 cp19
+        cp21 := comp cp20 cp20
+        main := comp cp21 cp21
+    ";
+
+    test_cases.push(Box::new(|| {
+        let program = util::program_from_string(PADDED_PROGRAM, &HashMap::new());
+        let (weight, _) = util::describe_cost(&program);
+        assert!(weight <= 4_000_050, "Program must cost at most MAX_BUDGET");
+        TestBuilder::comment("exec_budget/sufficient_padding")
+            .description("Expensive program has sufficient padding (C test vector)")
+            .program(&program)
+            .expected_error(ScriptError::Ok)
+            .finished()
+    }));
+
+    /*
+     * Success and failure differ only in the annex
+     *
+     * Both arms spend the same program (the one from above).
+     * The annex is not part of the program, so it cannot change the program's validity.
+     * It only changes the budget.
+     *
+     * The success arm has the annex that pads the budget to cover the cost.
+     * The failure arm has the minimal annex [0x50], which leaves the budget insufficient.
+     */
+    test_cases.push(Box::new(|| {
+        TestBuilder::comment("exec_budget/padding_only_in_annex")
+            .description("Success and failure differ only in the annex")
+            .human_encoding(PADDED_PROGRAM, &HashMap::new())
+            .expected_error(ScriptError::SimplicityExecBudget)
+            .finished_annex_pair(Some(vec![0x50]))
+    }));
+
+    /*
+     * This program is relatively cheap (116332 WU), but it takes ~1s to run
+     * The expected maximum runtime is 0.06s
+     */
+    fn program_cheap_but_slow() -> (Vec<u8>, Cmr) {
+        let mut unpack = Node::iden();
+        for _ in 0..15 {
+            unpack = Node::comp(&Node::take(&unpack), &Node::drop_(&unpack)).unwrap();
+        }
+        let program = Node::comp(
+            // Leave the witness value empty because
+            // we manually encode the witness block as the empty bitstring
+            &Node::witness(None),
+            &unpack,
+        )
+        .unwrap();
+        let bytes = simplicity::write_to_vec(|w| util::encode_program_empty_witness(&program, w));
+
+        (bytes, program.cmr())
+    }
+
+    test_cases.push(Box::new(|| {
+        TestBuilder::comment("ok/cheap_but_slow")
+            .description("Program is relatively cheap (116332 WU), but it takes ~1s to run")
+            .raw_program_cmr(program_cheap_but_slow())
+            .large_stack()
+            .expected_error(ScriptError::Ok)
+            .finished()
+    }));
+
+    super::generate(test_cases)
+}
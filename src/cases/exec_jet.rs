@@ -0,0 +1,537 @@
+//! # Jets
+//!
+//! Programs whose jets fail or succeed during execution, including introspection of the transaction.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use elements_miniscript::elements;
+use elements_miniscript::elements::hashes::{sha256, Hash};
+use elements_miniscript::elements::secp256k1_zkp;
+use simplicity::jet::Elements;
+use simplicity::{RedeemNode, Value};
+
+use super::Deferred;
+use crate::json::{ScriptError, TestCase};
+use crate::test::TestBuilder;
+use crate::util;
+use crate::witness::WitnessBuilder;
+
+/// Return the default test cases of this category.
+pub fn cases() -> impl Iterator<Item = TestCase> {
+    let mut test_cases: Vec<Deferred> = Vec::new();
+
+    /*
+     * Jet fails during its execution
+     */
+    test_cases.push(Box::new(|| {
+        let s = "
+            false := const 0b0
+            main := comp false jet_verify
+        ";
+        TestBuilder::comment("exec_jet/jet_verify_fails")
+            .description("Jet fails during its execution")
+            .human_encoding(s, &HashMap::new())
+            .expected_error(ScriptError::SimplicityExecJet)
+            .finished()
+    }));
+
+    /*
+     * Jet succeeds during its execution
+     */
+    test_cases.push(Box::new(|| {
+        let s = "
+            true := const 0b1
+            main := comp true jet_verify
+        ";
+        TestBuilder::comment("exec_jet/jet_verify_succeeds")
+            .description("Jet succeeds during its execution")
+            .human_encoding(s, &HashMap::new())
+            .expected_error(ScriptError::Ok)
+            .finished()
+    }));
+
+    /*
+     * Jet input comes from the witness
+     *
+     * wit:        1 → 2
+     * jet_verify: 2 → 1
+     *
+     * The spender supplies the bit that `jet_verify` checks.
+     * Witness value 1 passes and witness value 0 fails.
+     */
+    for (bit, error) in [(1, ScriptError::Ok), (0, ScriptError::SimplicityExecJet)] {
+        test_cases.push(Box::new(move || {
+            let s = "
+                wit := witness
+                main := comp wit jet_verify
+            ";
+            let witness = WitnessBuilder::new().value("wit", Value::u1(bit)).to_map();
+            TestBuilder::comment(format!("exec_jet/jet_verify_witness_{bit}"))
+                .description("Jet input comes from the witness")
+                .human_encoding(s, &witness)
+                .expected_error(error)
+                .finished()
+        }));
+    }
+
+    /*
+     * Arithmetic and hash jets compute the expected results
+     *
+     * Jets covered:
+     * - jet_add_32: 2 + 3 = 5
+     * - jet_subtract_32: 3 - 2 = 1
+     * - jet_multiply_32: 2 * 3 = 6
+     * - jet_divide_32: 6 / 2 = 3
+     * - jet_increment_32: 5 + 1 = 6
+     * - jet_sha_256_ctx_8_init, jet_sha_256_ctx_8_add_4, jet_sha_256_ctx_8_finalize:
+     *   SHA256(0x00000005)
+     * - jet_eq_32, jet_eq_64, jet_eq_256, jet_verify: check each result
+     */
+    test_cases.push(Box::new(|| {
+        let s = "
+            one := const 0x00000001
+            two := const 0x00000002
+            three := const 0x00000003
+            five := const 0x00000005
+            six := const 0x00000006
+            six_64 := const 0x0000000000000006
+            digest := const 0x221f8af2372a95064f2ef7d7712216a9ab46e7ef98482fd237e106f83eaa7569
+
+            sum := comp (comp (pair two three) jet_add_32) (drop iden)
+            sum_ok := comp (comp (pair sum five) jet_eq_32) jet_verify
+
+            difference := comp (comp (pair three two) jet_subtract_32) (drop iden)
+            difference_ok := comp (comp (pair difference one) jet_eq_32) jet_verify
+
+            product := comp (pair two three) jet_multiply_32
+            product_ok := comp (comp (pair product six_64) jet_eq_64) jet_verify
+
+            quotient := comp (pair six two) jet_divide_32
+            quotient_ok := comp (comp (pair quotient three) jet_eq_32) jet_verify
+
+            successor := comp (comp five jet_increment_32) (drop iden)
+            successor_ok := comp (comp (pair successor six) jet_eq_32) jet_verify
+
+            ctx := comp (pair jet_sha_256_ctx_8_init five) jet_sha_256_ctx_8_add_4
+            hash := comp ctx jet_sha_256_ctx_8_finalize
+            hash_ok := comp (comp (pair hash digest) jet_eq_256) jet_verify
+
+            arithmetic_ok := pair sum_ok (pair difference_ok (pair product_ok (pair quotient_ok successor_ok)))
+            main := comp (pair arithmetic_ok hash_ok) unit
+        ";
+        TestBuilder::comment("exec_jet/arithmetic_and_hash_jets")
+            .description("Arithmetic and hash jets compute the expected results")
+            .human_encoding(s, &HashMap::new())
+            .expected_error(ScriptError::Ok)
+            .finished()
+    }));
+
+    /*
+     * Hashlock: SHA256 of the witness preimage equals a committed hash
+     *
+     * The preimage is 32 bytes of 0x00 and the committed hash is its SHA256:
+     * 66687aadf862bd776c8fc18b8e9f8e20089714856ee233b3902a591d0d5f2925
+     *
+     * The correct preimage succeeds.
+     * The wrong preimage of 32 bytes of 0x01 makes `jet_verify` fail.
+     */
+    /// Program that succeeds if the SHA256 of the witness preimage equals the hash of `preimage`.
+    fn hashlock_program(preimage: [u8; 32]) -> String {
+        let hash = sha256::Hash::hash(&preimage);
+        format!(
+            "
+            wit := witness
+            ctx := comp (pair jet_sha_256_ctx_8_init wit) jet_sha_256_ctx_8_add_32
+            hash := comp ctx jet_sha_256_ctx_8_finalize
+            expected := const 0x{hash}
+            main := comp (comp (pair hash expected) jet_eq_256) jet_verify
+        "
+        )
+    }
+
+    let preimage = [0x00; 32];
+    for (wit_preimage, error, comment) in [
+        (preimage, ScriptError::Ok, "ok/hashlock_correct_preimage"),
+        (
+            [0x01; 32],
+            ScriptError::SimplicityExecJet,
+            "exec_jet/hashlock_wrong_preimage",
+        ),
+    ] {
+        test_cases.push(Box::new(move || {
+            assert_eq!(
+                "66687aadf862bd776c8fc18b8e9f8e20089714856ee233b3902a591d0d5f2925",
+                sha256::Hash::hash(&preimage).to_string()
+            );
+            let s = hashlock_program(preimage);
+            let witness = WitnessBuilder::new()
+                .value("wit", Value::u256_from_slice(&wit_preimage))
+                .to_map();
+            TestBuilder::comment(comment)
+                .description("Hashlock: SHA256 of the witness preimage equals a committed hash")
+                .human_encoding(&s, &witness)
+                .expected_error(error)
+                .finished()
+        }));
+    }
+
+    /*
+     * Timelocked signature: Schnorr signature and lock height are both checked
+     *
+     * The spender must provide a BIP 340 signature of `jet_sig_all_hash` under the committed key,
+     * and the lock time of the transaction must be at least the committed height 100.
+     * The lock time is enforced because the sequence of the input is below the maximum.
+     *
+     * The signature commits to the transaction, so each test case is first finished
+     * with a placeholder signature. The message is computed from this test case and signed,
+     * and then the test case is finished again with the real signature.
+     * Both signatures have the same length, so the transaction stays the same.
+     *
+     * - Correct signature, lock time 100: OK
+     * - Signature of the wrong message, lock time 100: `jet_bip_0340_verify` fails
+     * - Correct signature, lock time 99: `jet_check_lock_height` fails
+     */
+    let lock_height = 100;
+    for (sign_sighash, lock_time, error, comment) in [
+        (
+            true,
+            lock_height,
+            ScriptError::Ok,
+            "ok/timelocked_signature",
+        ),
+        (
+            false,
+            lock_height,
+            ScriptError::SimplicityExecJet,
+            "exec_jet/timelocked_signature_wrong_message",
+        ),
+        (
+            true,
+            lock_height - 1,
+            ScriptError::SimplicityExecJet,
+            "exec_jet/timelocked_signature_too_early",
+        ),
+    ] {
+        test_cases.push(Box::new(move || {
+            let secp = secp256k1_zkp::Secp256k1::new();
+            let keypair = secp256k1_zkp::Keypair::from_seckey_slice(&secp, &[0x01; 32])
+                .expect("valid secret key");
+            let (public_key, _) = keypair.x_only_public_key();
+            let s = format!(
+                "
+                sig := witness
+                pk := const 0x{public_key}
+                sig_ok := comp (pair (pair pk jet_sig_all_hash) sig) jet_bip_0340_verify
+                height := const 0x{lock_height:08x}
+                lock_ok := comp height jet_check_lock_height
+                main := comp (pair sig_ok lock_ok) unit
+            "
+            );
+            // The placeholder signature is invalid, so it is not checked
+            let finished = |signature: [u8; 64], self_check: bool| {
+                let witness = WitnessBuilder::new()
+                    .value("sig", Value::power_of_two(&signature))
+                    .to_map();
+                TestBuilder::comment(comment)
+                    .description(
+                        "Timelocked signature: Schnorr signature and lock height are both checked",
+                    )
+                    .human_encoding(&s, &witness)
+                    .self_check(self_check)
+                    .sequence(elements::Sequence::ENABLE_LOCKTIME_NO_RBF)
+                    .lock_time(elements::LockTime::from_height(lock_time).expect("valid height"))
+                    .expected_error(error)
+                    .finished()
+            };
+            let placeholder = finished([0; 64], false);
+            let message = match sign_sighash {
+                true => placeholder.sighash_all(),
+                false => [0; 32],
+            };
+            let message = secp256k1_zkp::Message::from_digest(message);
+            let signature = secp.sign_schnorr_no_aux_rand(&message, &keypair);
+            let test_case = finished(signature.serialize(), true);
+            assert_eq!(placeholder.tx, test_case.tx);
+            test_case
+        }));
+    }
+
+    /*
+     * Jet reads the relative lock time from the sequence of the input
+     *
+     * `jet_check_lock_distance` fails unless the sequence of the current input
+     * encodes a relative lock of at least the given number of blocks.
+     * The program requires a distance of 10 blocks.
+     *
+     * - Sequence with a relative lock of 10 blocks: OK
+     * - Sequence with a relative lock of 9 blocks: `jet_check_lock_distance` fails
+     */
+    for (distance, error, comment) in [
+        (10, ScriptError::Ok, "ok/lock_distance_reached"),
+        (
+            9,
+            ScriptError::SimplicityExecJet,
+            "exec_jet/lock_distance_not_reached",
+        ),
+    ] {
+        test_cases.push(Box::new(move || {
+            let s = "main := comp (const 0x000a) jet_check_lock_distance";
+            TestBuilder::comment(comment)
+                .description("Jet reads the relative lock time from the sequence of the input")
+                .human_encoding(s, &HashMap::new())
+                .sequence(elements::Sequence::from_height(distance))
+                .expected_error(error)
+                .finished()
+        }));
+    }
+
+    /*
+     * Jet reads the index of the current input
+     *
+     * The program succeeds if the Simplicity input is at index 1.
+     * A dummy input is placed before or after the Simplicity input.
+     */
+    for (index, comment, error) in [
+        (1, "ok/current_index_1", ScriptError::Ok),
+        (
+            0,
+            "exec_jet/current_index_0",
+            ScriptError::SimplicityExecJet,
+        ),
+    ] {
+        test_cases.push(Box::new(move || {
+            let s = "main := comp (comp (pair jet_current_index (const 0x00000001)) jet_eq_32) jet_verify";
+            let dummy_input = elements::TxIn {
+                previous_output: elements::OutPoint::new(elements::Txid::all_zeros(), 1),
+                ..Default::default()
+            };
+            TestBuilder::comment(comment)
+                .description("Jet reads the index of the current input")
+                .human_encoding(s, &HashMap::new())
+                .extra_inputs(vec![(dummy_input, elements::TxOut::default())])
+                .input_index(index)
+                .expected_error(error)
+                .finished()
+        }));
+    }
+
+    /*
+     * Jet reads the annex
+     *
+     * `jet_current_annex_hash` returns the SHA256 hash of the annex of the current input,
+     * including the leading 0x50 byte, or nothing if there is no annex.
+     *
+     * The program succeeds if the annex is 0x50cafebabe.
+     * The annex 0x50deadbeef makes `jet_verify` fail.
+     * Both annexes are long enough to cover the program cost.
+     */
+    /// Program that succeeds if the hash of the annex equals `expected_hash`.
+    /// A missing annex fails the assertion.
+    fn annex_hash_program(expected_hash: sha256::Hash) -> Arc<RedeemNode<Elements>> {
+        let s = format!(
+            "
+            expected := const 0x{expected_hash}
+            is_expected := comp (pair (take iden) (comp unit expected)) jet_eq_256
+            is_expected_annex := comp (pair jet_current_annex_hash unit) (assertr #{{unit}} is_expected)
+            main := comp is_expected_annex jet_verify
+        "
+        );
+        util::program_from_string(&s, &HashMap::new())
+    }
+
+    let expected_annex = [0x50, 0xca, 0xfe, 0xba, 0xbe];
+    for (annex, error, comment) in [
+        (
+            expected_annex,
+            ScriptError::Ok,
+            "exec_jet/annex_hash_matches",
+        ),
+        (
+            [0x50, 0xde, 0xad, 0xbe, 0xef],
+            ScriptError::SimplicityExecJet,
+            "exec_jet/annex_hash_mismatches",
+        ),
+    ] {
+        test_cases.push(Box::new(move || {
+            let expected_hash = sha256::Hash::hash(&expected_annex);
+            TestBuilder::comment(comment)
+                .description("Jet reads the annex")
+                .program(&annex_hash_program(expected_hash))
+                // rust-simplicity does not pass the annex to the jets
+                .self_check(false)
+                .annex(annex.to_vec())
+                .expected_error(error)
+                .finished()
+        }));
+    }
+
+    /*
+     * Jet reads the number of outputs
+     *
+     * The spending transaction has three null outputs instead of the single dummy output.
+     * `jet_num_outputs` returns the number of outputs as a 32-bit word.
+     */
+    test_cases.push(Box::new(|| {
+        let s = "
+            three := const 0x00000003
+            main := comp (comp (pair jet_num_outputs three) jet_eq_32) jet_verify
+        ";
+        TestBuilder::comment("exec_jet/num_outputs")
+            .description("Jet reads the number of outputs")
+            .human_encoding(s, &HashMap::new())
+            .outputs(vec![elements::TxOut::default(); 3])
+            .expected_error(ScriptError::Ok)
+            .finished()
+    }));
+
+    /*
+     * Jet reads the sequence of the current input
+     *
+     * `jet_current_sequence` returns the sequence number of the current input as a 32-bit word.
+     *
+     * The program succeeds if the sequence is 0xfffffffd (opt-in replace-by-fee).
+     * The sequence 0xfffffffe makes `jet_verify` fail.
+     */
+    let expected_sequence = elements::Sequence(0xfffffffd);
+    for (sequence, error, comment) in [
+        (
+            expected_sequence,
+            ScriptError::Ok,
+            "exec_jet/current_sequence_matches",
+        ),
+        (
+            elements::Sequence(0xfffffffe),
+            ScriptError::SimplicityExecJet,
+            "exec_jet/current_sequence_mismatches",
+        ),
+    ] {
+        test_cases.push(Box::new(move || {
+            let s = format!(
+                "
+                expected := const 0x{:08x}
+                main := comp (comp (pair jet_current_sequence expected) jet_eq_32) jet_verify
+            ",
+                expected_sequence.0
+            );
+            TestBuilder::comment(comment)
+                .description("Jet reads the sequence of the current input")
+                .human_encoding(&s, &HashMap::new())
+                .sequence(sequence)
+                .expected_error(error)
+                .finished()
+        }));
+    }
+
+    /*
+     * Program derives an asset ID from the genesis block hash and compares it to the prevout asset
+     *
+     * The program treats the genesis block hash as the txid of an issuance outpoint:
+     *
+     * entropy  = calculate_issuance_entropy((genesis block hash, vout 0), contract hash 0)
+     * asset ID = calculate_asset(entropy)
+     *
+     * The asset of the spent prevout is explicit, which is the right branch of
+     * `jet_current_asset`, so `assertr` takes it and hides the confidential branch.
+     * The spend succeeds on the chain whose genesis block hash derives the prevout asset,
+     * and it fails on any other chain, such as the default chain of the script assets tests.
+     */
+    /// Return the program, the Liquid genesis block hash and the asset that it derives.
+    fn asset_from_genesis_hash() -> (String, elements::BlockHash, elements::confidential::Asset) {
+        let s = format!(
+            "
+            outpoint := pair jet_genesis_block_hash (const 0x00000000)
+            entropy := comp (pair outpoint (const 0x{zero})) jet_calculate_issuance_entropy
+            derived := comp entropy jet_calculate_asset
+            explicit := comp (pair jet_current_asset unit) (assertr #{{take iden}} (take iden))
+            main := comp (comp (pair derived explicit) jet_eq_256) jet_verify
+        ",
+            zero = "00".repeat(32)
+        );
+        // Genesis block hash of the Liquid network
+        let genesis_hash: elements::BlockHash =
+            "1466275836220db2944ca059a3a10ef6fd2ea684b0688d2c379296888a206003"
+                .parse()
+                .expect("valid block hash");
+        let outpoint =
+            elements::OutPoint::new(elements::Txid::from_raw_hash(genesis_hash.to_raw_hash()), 0);
+        let entropy = elements::AssetId::generate_asset_entropy(
+            outpoint,
+            elements::ContractHash::all_zeros(),
+        );
+        let asset =
+            elements::confidential::Asset::Explicit(elements::AssetId::from_entropy(entropy));
+        (s, genesis_hash, asset)
+    }
+
+    test_cases.push(Box::new(|| {
+        let (s, genesis_hash, asset) = asset_from_genesis_hash();
+        TestBuilder::comment("ok/asset_from_genesis_hash")
+            .description("Program derives an asset ID from the genesis block hash")
+            .human_encoding(&s, &HashMap::new())
+            .genesis_hash(genesis_hash)
+            .prevout_asset(asset)
+            .expected_error(ScriptError::Ok)
+            .finished()
+    }));
+
+    test_cases.push(Box::new(|| {
+        let (s, _, asset) = asset_from_genesis_hash();
+        TestBuilder::comment("exec_jet/asset_from_default_genesis_hash")
+            .description("Program derives an asset ID from a different genesis block hash")
+            .human_encoding(&s, &HashMap::new())
+            .prevout_asset(asset)
+            .expected_error(ScriptError::SimplicityExecJet)
+            .finished()
+    }));
+
+    /*
+     * Jets read and sum the amounts of two inputs
+     *
+     * Read the explicit amounts of inputs 0 and 1 with `jet_input_amount`,
+     * add them with `jet_add_64` and compare the sum with the constant 3000:
+     * OK if the prevout values add up to the constant, SIMPLICITY_EXEC_JET otherwise.
+     *
+     * The Simplicity input at index 0 spends 1000. The dummy input at index 1 spends the rest.
+     */
+    for (dummy_amount, comment, error) in [
+        (2000, "ok/input_amounts_sum", ScriptError::Ok),
+        (
+            2001,
+            "exec_jet/input_amounts_sum_mismatch",
+            ScriptError::SimplicityExecJet,
+        ),
+    ] {
+        test_cases.push(Box::new(move || {
+            let s = "
+                input_amount := comp (pair jet_input_amount unit) (assertr #{unit} (take iden))
+                explicit_amount := comp (pair (comp input_amount (drop iden)) unit) (assertr #{unit} (take iden))
+                amount0 := comp (const 0x00000000) explicit_amount
+                amount1 := comp (const 0x00000001) explicit_amount
+                sum := comp (comp (pair amount0 amount1) jet_add_64) (drop iden)
+                main := comp (comp (pair sum (const 0x0000000000000bb8)) jet_eq_64) jet_verify
+            ";
+            let dummy_input = elements::TxIn {
+                previous_output: elements::OutPoint::new(elements::Txid::all_zeros(), 1),
+                ..Default::default()
+            };
+            let dummy_prevout = elements::TxOut {
+                value: elements::confidential::Value::Explicit(dummy_amount),
+                ..Default::default()
+            };
+            TestBuilder::comment(comment)
+                .description("Jets read and sum the amounts of two inputs")
+                .human_encoding(s, &HashMap::new())
+                .prevout(
+                    elements::confidential::Asset::Null,
+                    elements::confidential::Value::Explicit(1000),
+                    None,
+                )
+                .extra_inputs(vec![(dummy_input, dummy_prevout)])
+                .expected_error(error)
+                .finished()
+        }));
+    }
+
+    super::generate(test_cases)
+}
@@ -0,0 +1,165 @@
+//! # Execution memory
+//!
+//! Programs that use more memory than CELLS_MAX.
+
+use std::collections::HashMap;
+
+use simplicity::jet::{Elements, Jet};
+use simplicity::Cmr;
+
+use super::Deferred;
+use crate::bit_encoding::BitBuilder;
+use crate::json::{ScriptError, TestCase};
+use crate::test::TestBuilder;
+use crate::util;
+
+/// Return the default test cases of this category.
+pub fn cases() -> impl Iterator<Item = TestCase> {
+    let mut test_cases: Vec<Deferred> = Vec::new();
+
+    /*
+     * Program uses more memory than static maximum (CELLS_MAX) (C test vector)
+     */
+    test_cases.push(Box::new(|| {
+        // comp (const word of 2^23 zero bits) unit
+        let bytes = BitBuilder::program_preamble(3)
+            .repeated_byte_word(24, 0x00)
+            .unit()
+            .comp(2, 1)
+            .witness_preamble(0)
+            .program_finished();
+        assert_eq!((1 << 20) + 4, bytes.len());
+        let cmr = Cmr::from_byte_array([
+            0x7f, 0x81, 0xc0, 0x76, 0xf0, 0xdf, 0x95, 0x05, 0xbf, 0xce, 0x61, 0xf0, 0x41, 0x19,
+            0x7b, 0xd9, 0x2a, 0xaa, 0xa4, 0xf1, 0x70, 0x15, 0xd1, 0xec, 0xb2, 0x48, 0xdd, 0xff,
+            0xe9, 0xd9, 0xda, 0x07,
+        ]);
+        TestBuilder::comment("exec_memory/memory_usage_exceeds_max_cells")
+            .description("Program uses more memory than static maximum (CELLS_MAX) (C test vector)")
+            .note("Test vector from the C implementation of Simplicity")
+            .raw_program(bytes)
+            .raw_cmr(cmr)
+            // The CMR is taken from the C test vector;
+            // decoding the constant of 2^23 bits takes seconds
+            .cmr_check(false)
+            .expected_error(ScriptError::SimplicityExecMemory)
+            .finished()
+    }));
+
+    /*
+     * Program uses more memory than CELLS_MAX through many live frames
+     *
+     * The cases above exceed CELLS_MAX with a single large value.
+     * Here, no value is larger than CELLS_MAX, but many values are held at the same time.
+     */
+    /// Return a program that doubles a 5-bit value `n_doublings` times and then drops it.
+    ///
+    /// dup_i := pair iden iden: 2^(i-1) × 5 bits → 2^i × 5 bits
+    /// d_0   := pair (const 0b0) (const 0x0): 5 bits
+    /// d_i   := comp d_(i-1) dup_i
+    /// main  := comp d_n unit
+    ///
+    /// The doublings are nested to the left, so each `comp` allocates the write frame
+    /// for its output before it executes its left child. While `d_0` executes,
+    /// the write frames of all enclosing `comp` nodes are live, which is 5 × (2^(n+1) - 1) cells.
+    /// Tail call optimization does not help, because no read frame can be dropped early.
+    fn doubling_program(n_doublings: usize) -> String {
+        let mut s = String::from("d0 := pair (const 0b0) (const 0x0)\n");
+        for i in 1..=n_doublings {
+            s.push_str(&format!("dup{i} := pair iden iden\n"));
+            s.push_str(&format!("d{i} := comp d{} dup{i}\n", i - 1));
+        }
+        s.push_str(&format!("main := comp d{n_doublings} unit"));
+        s
+    }
+
+    const CELLS_MAX: usize = 0x500000;
+    for (n_doublings, expected_error, comment) in [
+        (19, ScriptError::Ok, "ok/live_frames_just_below_max_cells"),
+        (
+            20,
+            ScriptError::SimplicityExecMemory,
+            "exec_memory/live_frames_exceed_max_cells",
+        ),
+    ] {
+        test_cases.push(Box::new(move || {
+            let s = doubling_program(n_doublings);
+            let program = util::program_from_string(&s, &HashMap::new());
+            let (_, cells) = util::describe_cost(&program);
+            assert_eq!(5 * ((1 << (n_doublings + 1)) - 1), cells);
+            assert_eq!(expected_error == ScriptError::Ok, cells <= CELLS_MAX);
+            TestBuilder::comment(comment)
+                .description("Program uses more memory than CELLS_MAX through many live frames")
+                .human_encoding(&s, &HashMap::new())
+                .expected_error(expected_error)
+                .finished()
+        }));
+    }
+
+    super::generate(test_cases)
+}
+
+/// Return the test case with a real witness block of 2^28 bits.
+pub fn large_witness_cases() -> impl Iterator<Item = TestCase> {
+    let mut test_cases: Vec<Deferred> = Vec::new();
+
+    /*
+     * Witness block of 2^28 real bits (32 MiB)
+     *
+     * The cases above only declare a large witness length.
+     * This program contains a witness value of 2^28 bits that is actually written out.
+     *
+     * g0 := jet_verify                               : 2 → 1
+     * g(k+1) := comp (pair (take gk) (drop gk)) unit : 2^(2^k) × 2^(2^k) → 1
+     * main := comp witness g28                       : 1 → 1
+     *
+     * The witness value has 2^28 bits, which exceeds CELLS_MAX.
+     * Static analysis fails only after the witness block has been parsed and type-checked.
+     */
+    /// Program that consumes a witness value of 2^log_bit_len bits, all of which are ones.
+    fn large_witness_program(log_bit_len: usize) -> (Vec<u8>, Cmr) {
+        assert!(3 <= log_bit_len, "Witness value must fill whole bytes");
+        let len = 4 * log_bit_len + 4;
+        let mut builder = BitBuilder::program_preamble(len)
+            .witness()
+            .named_jet(Elements::Verify);
+        let mut cmr = Elements::Verify.cmr();
+        let (mut g, mut unit) = (1, None);
+
+        for _ in 0..log_bit_len {
+            let i = g + 1;
+            builder = builder.take(i - g).drop(i + 1 - g).pair(2, 1);
+            builder = match unit {
+                None => {
+                    unit = Some(i + 3);
+                    g = i + 4;
+                    builder.unit().comp(2, 1)
+                }
+                Some(unit) => {
+                    g = i + 3;
+                    builder.comp(1, i + 3 - unit)
+                }
+            };
+            cmr = Cmr::comp(Cmr::pair(Cmr::take(cmr), Cmr::drop(cmr)), Cmr::unit());
+        }
+
+        assert_eq!(len - 1, g + 1);
+        let bytes = builder
+            .comp(len - 1, 1)
+            .witness_preamble(1 << log_bit_len)
+            .bytes_be(vec![0xff; 1 << (log_bit_len - 3)])
+            .program_finished();
+        let cmr = Cmr::comp(Cmr::witness(), cmr);
+        (bytes, cmr)
+    }
+
+    test_cases.push(Box::new(|| {
+        TestBuilder::comment("exec_memory/witness_block_2_28_real_bits")
+            .description("Witness block of 2^28 real bits (32 MiB)")
+            .raw_program_cmr(large_witness_program(28))
+            .expected_error(ScriptError::SimplicityExecMemory)
+            .finished()
+    }));
+
+    super::generate(test_cases)
+}
@@ -0,0 +1,48 @@
+//! # Fail and stop codes
+//!
+//! Programs that contain a `fail` node or the stop code.
+
+use simplicity::{Cmr, FailEntropy};
+
+use super::Deferred;
+use crate::bit_encoding::BitBuilder;
+use crate::json::{ScriptError, TestCase};
+use crate::test::TestBuilder;
+
+/// Return the default test cases of this category.
+pub fn cases() -> impl Iterator<Item = TestCase> {
+    let mut test_cases: Vec<Deferred> = Vec::new();
+
+    /*
+     * Program contains a `fail` node
+     */
+    test_cases.push(Box::new(|| {
+        let entropy = FailEntropy::from_byte_array([0; 64]);
+        let bytes = BitBuilder::program_preamble(1)
+            .fail(entropy)
+            .witness_preamble(0)
+            .program_finished();
+        let cmr = Cmr::fail(entropy);
+        TestBuilder::comment("fail_code/fail_node")
+            .description("Program contains a `fail` node")
+            .raw_program(bytes)
+            .raw_cmr(cmr)
+            .expected_error(ScriptError::SimplicityFailCode)
+            .finished()
+    }));
+
+    /*
+     * Program contains the stop code
+     */
+    test_cases.push(Box::new(|| {
+        let bytes = BitBuilder::program_preamble(1).stop().parser_stops_here();
+        TestBuilder::comment("stop_code/stop_code")
+            .description("Program contains the stop code")
+            .raw_program(bytes)
+            .raw_cmr([0; 32])
+            .expected_error(ScriptError::SimplicityStopCode)
+            .finished()
+    }));
+
+    super::generate(test_cases)
+}
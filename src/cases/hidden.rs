@@ -0,0 +1,141 @@
+//! # Hidden nodes
+//!
+//! Programs with hidden nodes in illegal positions.
+
+use simplicity::{Cmr, Value};
+
+use super::Deferred;
+use crate::bit_encoding::BitBuilder;
+use crate::json::{ScriptError, TestCase};
+use crate::test::TestBuilder;
+
+/// Return the default test cases of this category.
+pub fn cases() -> impl Iterator<Item = TestCase> {
+    let mut test_cases: Vec<Deferred> = Vec::new();
+
+    /*
+     * Left child of composition is hidden
+     */
+    /// Program causes SIMPLICITY_HIDDEN iff left_hidden is true
+    fn comp_hidden_child_program(left_hidden: bool) -> (Vec<u8>, Cmr) {
+        let unit = Cmr::unit();
+        let mut builder = BitBuilder::program_preamble(2);
+
+        if left_hidden {
+            builder = builder.hidden(unit).comp(1, 1);
+        } else {
+            builder = builder.unit().comp(1, 1);
+        }
+
+        let bytes = builder.witness_preamble(0).program_finished();
+        let cmr = Cmr::comp(unit, unit);
+
+        (bytes, cmr)
+    }
+
+    test_cases.push(Box::new(|| {
+        TestBuilder::comment("hidden/comp_left_hidden")
+            .description("Left child of composition is hidden")
+            .raw_program_cmr(comp_hidden_child_program(true))
+            .expected_error(ScriptError::SimplicityHidden)
+            .finished()
+    }));
+
+    /*
+     * No child of composition is hidden
+     */
+    test_cases.push(Box::new(|| {
+        TestBuilder::comment("hidden/comp_nothing_hidden")
+            .description("No child of composition is hidden")
+            .raw_program_cmr(comp_hidden_child_program(false))
+            .expected_error(ScriptError::Ok)
+            .finished()
+    }));
+
+    /*
+     * Both children of case are hidden
+     */
+    fn case_hidden_child_program(both_hidden: bool, hide_left: bool) -> (Vec<u8>, Cmr) {
+        let take_unit = Cmr::take(Cmr::unit());
+        let value = Value::u1(u8::from(hide_left));
+
+        let mut builder = BitBuilder::program_preamble(7)
+            .word(1, &value) // 1 → 2
+            .unit() // 1 → 1
+            .pair(2, 1); // 1 → 2 × 1
+        let cmr = Cmr::pair(Cmr::const_word(&value), Cmr::unit());
+
+        if both_hidden {
+            builder = builder
+                .hidden(take_unit)
+                .hidden(take_unit)
+                .case(2, 1) // (1 + 1) × 1 → 1
+                .comp(4, 1); // 1 → 1
+        } else if !hide_left {
+            builder = builder
+                .take(2) // 1 × 1 → 1
+                .hidden(take_unit)
+                .case(2, 1) // (1 + 1) × 1 → 1
+                .comp(4, 1); // 1 → 1
+        } else {
+            builder = builder
+                .hidden(take_unit)
+                .take(3) // 1 × 1 → 1
+                .case(2, 1) // (1 + 1) × 1 → 1
+                .comp(4, 1); // 1 → 1
+        }
+
+        let bytes = builder.witness_preamble(0).program_finished();
+        let cmr = Cmr::comp(cmr, Cmr::case(take_unit, take_unit));
+
+        (bytes, cmr)
+    }
+
+    test_cases.push(Box::new(|| {
+        TestBuilder::comment("hidden/case_both_hidden")
+            .description("Both children of case are hidden")
+            .raw_program_cmr(case_hidden_child_program(true, bool::default()))
+            .expected_error(ScriptError::SimplicityHidden)
+            .finished()
+    }));
+
+    /*
+     * Left child of case is hidden
+     */
+    test_cases.push(Box::new(|| {
+        TestBuilder::comment("hidden/case_left_hidden")
+            .description("Left child of case is hidden")
+            .raw_program_cmr(case_hidden_child_program(false, false))
+            .expected_error(ScriptError::Ok)
+            .finished()
+    }));
+
+    /*
+     * Right child of case is hidden
+     */
+    test_cases.push(Box::new(|| {
+        TestBuilder::comment("hidden/case_right_hidden")
+            .description("Right child of case is hidden")
+            .raw_program_cmr(case_hidden_child_program(false, true))
+            .expected_error(ScriptError::Ok)
+            .finished()
+    }));
+
+    /*
+     * Program root is hidden
+     */
+    test_cases.push(Box::new(|| {
+        let hidden_cmr = Cmr::from_byte_array([0; 32]);
+        let bytes = BitBuilder::program_preamble(1)
+            .hidden(hidden_cmr)
+            .parser_stops_here();
+        TestBuilder::comment("hidden_root/hidden_root")
+            .description("Program root is hidden")
+            .raw_program(bytes)
+            .raw_cmr(hidden_cmr)
+            .expected_error(ScriptError::SimplicityHiddenRoot)
+            .finished()
+    }));
+
+    super::generate(test_cases)
+}
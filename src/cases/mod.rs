@@ -0,0 +1,34 @@
+//! # Test cases
+//!
+//! Each module generates the test cases of one category.
+//! The binary concatenates the categories.
+//!
+//! Categories return iterators that generate one test case at a time,
+//! so only the test case that is being written is held in memory.
+
+use crate::json::TestCase;
+
+pub mod antidos;
+pub mod bitstream_eof;
+pub mod bitstream_unused;
+pub mod cmr;
+pub mod combinators;
+pub mod data_out_of_order;
+pub mod data_out_of_range;
+pub mod exec_budget;
+pub mod exec_jet;
+pub mod exec_memory;
+pub mod fail_stop;
+pub mod hidden;
+pub mod type_inference;
+pub mod unshared_subexpression;
+pub mod witness_block;
+pub mod witness_stack;
+
+/// Test case that is generated once the iterator reaches it.
+pub type Deferred = Box<dyn FnOnce() -> TestCase>;
+
+/// Return an iterator that generates the given test cases in order.
+pub fn generate(test_cases: Vec<Deferred>) -> impl Iterator<Item = TestCase> {
+    test_cases.into_iter().map(|test_case| test_case())
+}
@@ -0,0 +1,419 @@
+//! # Type inference
+//!
+//! Programs whose types fail to unify, are infinite, or are not `1 → 1` at the root.
+
+use simplicity::jet::{Elements, Jet};
+use simplicity::{Cmr, Value};
+
+use super::Deferred;
+use crate::bit_encoding::BitBuilder;
+use crate::json::{ScriptError, TestCase};
+use crate::test::TestBuilder;
+use crate::witness::WitnessBuilder;
+
+/// Return the default test cases of this category.
+pub fn cases() -> impl Iterator<Item = TestCase> {
+    let mut test_cases: Vec<Deferred> = Vec::new();
+
+    /*
+     * Comp combinator: left target != right source
+     *
+     * unit:      A     → 1
+     * take unit: 1 × B → 1
+     * comp unit (take unit) fails to unify
+     */
+    test_cases.push(Box::new(|| {
+        let bytes = BitBuilder::program_preamble(3)
+            .unit()
+            .take(1)
+            .comp(2, 1)
+            .witness_preamble(0)
+            .program_finished();
+        let cmr = Cmr::comp(Cmr::unit(), Cmr::take(Cmr::unit()));
+        TestBuilder::comment("type_inference_unification/comp_unify_left_target_right_source")
+            .description("Comp combinator: left target != right source")
+            .raw_program(bytes)
+            .raw_cmr(cmr)
+            .expected_error(ScriptError::SimplicityTypeInferenceUnification)
+            .finished()
+    }));
+
+    /*
+     * Pair combinator: left source != right source
+     *
+     * word(0):    1     → 2 = 1 + 1
+     * take unit:  A × B → 1
+     * pair word(0) (take unit) fails to unify
+     */
+    test_cases.push(Box::new(|| {
+        let value = Value::u1(0);
+        let bytes = BitBuilder::program_preamble(4)
+            .word(1, &value)
+            .unit()
+            .take(1)
+            .pair(3, 1)
+            .witness_preamble(0)
+            .program_finished();
+        let cmr = Cmr::pair(Cmr::const_word(&value), Cmr::take(Cmr::unit()));
+        TestBuilder::comment("type_inference_unification/pair_unify_left_source_right_source")
+            .description("Pair combinator: left source != right source")
+            .raw_program(bytes)
+            .raw_cmr(cmr)
+            .expected_error(ScriptError::SimplicityTypeInferenceUnification)
+            .finished()
+    }));
+
+    /*
+     * Case combinator: left target != right target
+     *
+     * take word(0):  A × 1 → 2^1
+     * take word(00): A × 1 → 2^2
+     * case (take word(0)) (take word(00)) fails to unify
+     */
+    test_cases.push(Box::new(|| {
+        let small_value = Value::u1(0);
+        let large_value = Value::u2(0);
+        let bytes = BitBuilder::program_preamble(5)
+            .word(1, &small_value)
+            .take(1)
+            .word(2, &large_value)
+            .take(1)
+            .case(3, 1)
+            .witness_preamble(0)
+            .program_finished();
+        let cmr = Cmr::case(
+            Cmr::take(Cmr::const_word(&small_value)),
+            Cmr::take(Cmr::const_word(&large_value)),
+        );
+        TestBuilder::comment("type_inference_unification/case_unify_left_target_right_target")
+            .description("Case combinator: left target != right target")
+            .raw_program(bytes)
+            .raw_cmr(cmr)
+            .expected_error(ScriptError::SimplicityTypeInferenceUnification)
+            .finished()
+    }));
+
+    /*
+     * Case combinator: left source != A × C
+     *
+     * word(0):   1     → 2
+     * take unit: B × C → 1
+     * case word(0) (take unit) fails to unify
+     */
+    test_cases.push(Box::new(|| {
+        let value = Value::u1(0);
+        let bytes = BitBuilder::program_preamble(4)
+            .word(1, &value)
+            .unit()
+            .take(1)
+            .case(3, 1)
+            .witness_preamble(0)
+            .program_finished();
+        let cmr = Cmr::case(Cmr::const_word(&value), Cmr::take(Cmr::unit()));
+        TestBuilder::comment("type_inference_unification/case_bind_left_target")
+            .description("Case combinator: left source != A × C")
+            .raw_program(bytes)
+            .raw_cmr(cmr)
+            .expected_error(ScriptError::SimplicityTypeInferenceUnification)
+            .finished()
+    }));
+
+    /*
+     * Case combinator: right source != B × C
+     *
+     * take unit: B × C → 1
+     * word(0):   1     → 2
+     * case (take unit) word(0) fails to unify
+     */
+    test_cases.push(Box::new(|| {
+        let value = Value::u1(0);
+        let bytes = BitBuilder::program_preamble(4)
+            .unit()
+            .take(1)
+            .word(1, &value)
+            .case(2, 1)
+            .witness_preamble(0)
+            .program_finished();
+        let cmr = Cmr::case(Cmr::take(Cmr::unit()), Cmr::const_word(&value));
+        TestBuilder::comment("type_inference_unification/case_bind_right_target")
+            .description("Case combinator: right source != B × C")
+            .raw_program(bytes)
+            .raw_cmr(cmr)
+            .expected_error(ScriptError::SimplicityTypeInferenceUnification)
+            .finished()
+    }));
+
+    /*
+     * Witness type is inferred from how the witness is consumed
+     *
+     * witness:                        1     → A
+     * pair (take verify) (drop verify): 2 × 2 → 1 × 1
+     *
+     * Nothing but the consumer constrains A, which unifies with 2 × 2.
+     * The witness value is encoded at this type.
+     */
+    test_cases.push(Box::new(|| {
+        let s = "
+            wit := witness
+            main := comp wit (comp (pair (take jet_verify) (drop jet_verify)) unit)
+        ";
+        let witness = WitnessBuilder::new()
+            .value("wit", Value::prod(Value::u1(1), Value::u1(1)))
+            .to_map();
+        TestBuilder::comment("ok/witness_type_from_consumer")
+            .description("Witness type is inferred from how the witness is consumed")
+            .human_encoding(s, &witness)
+            .expected_error(ScriptError::Ok)
+            .finished()
+    }));
+
+    /*
+     * Witness type cannot satisfy both of its consumers
+     *
+     * witness:   1     → A
+     * verify:    2     → 1
+     * take unit: B × C → 1
+     *
+     * The witness node is shared, so it has a single type A.
+     * comp witness verify binds A to 2 = 1 + 1,
+     * and comp witness (take unit) binds A to a product, which fails to unify.
+     *
+     * The human encoding rejects a witness name that is used twice,
+     * so the program is built from bits.
+     */
+    test_cases.push(Box::new(|| {
+        let bytes = BitBuilder::program_preamble(8)
+            .witness()
+            .named_jet(Elements::Verify)
+            .comp(2, 1)
+            .unit()
+            .take(1)
+            .comp(5, 1)
+            .pair(4, 1)
+            .comp(1, 4)
+            .witness_preamble(0)
+            .program_finished();
+        let cmr = Cmr::comp(
+            Cmr::pair(
+                Cmr::comp(Cmr::witness(), Elements::Verify.cmr()),
+                Cmr::comp(Cmr::witness(), Cmr::take(Cmr::unit())),
+            ),
+            Cmr::unit(),
+        );
+        TestBuilder::comment("type_inference_unification/witness_type_conflicting_consumers")
+            .description("Witness type cannot satisfy both of its consumers")
+            .raw_program(bytes)
+            .raw_cmr(cmr)
+            .expected_error(ScriptError::SimplicityTypeInferenceUnification)
+            .finished()
+    }));
+
+    /*
+     * Disconnect combinator: left source != 2^256 × A
+     *
+     * word(0): 1 → 2
+     * iden   : C → D
+     * disconnect word(0) iden fails to unify
+     */
+    test_cases.push(Box::new(|| {
+        let value = Value::u1(0);
+        let bytes = BitBuilder::program_preamble(3)
+            .word(1, &value)
+            .iden()
+            .disconnect(2, 1)
+            .witness_preamble(0)
+            .program_finished();
+        let cmr = Cmr::disconnect(Cmr::const_word(&value));
+        TestBuilder::comment("type_inference_unification/disconnect_bind_left_source")
+            .description("Disconnect combinator: left source != 2^256 × A")
+            .raw_program(bytes)
+            .raw_cmr(cmr)
+            .expected_error(ScriptError::SimplicityTypeInferenceUnification)
+            .finished()
+    }));
+
+    /*
+     * Disconnect combinator: left target != B × C
+     *
+     * unit: A → 1
+     * iden: C → D
+     * disconnect unit iden fails to unify
+     */
+    test_cases.push(Box::new(|| {
+        let bytes = BitBuilder::program_preamble(3)
+            .unit()
+            .iden()
+            .disconnect(2, 1)
+            .witness_preamble(0)
+            .program_finished();
+        let cmr = Cmr::disconnect(Cmr::unit());
+        TestBuilder::comment("type_inference_unification/disconnect_bind_left_target")
+            .description("Disconnect combinator: left target != B × C")
+            .raw_program(bytes)
+            .raw_cmr(cmr)
+            .expected_error(ScriptError::SimplicityTypeInferenceUnification)
+            .finished()
+    }));
+
+    /*
+     * Infinite type is inferred
+     *
+     * drop iden: A × B → B
+     * iden:      C     → C
+     * case (drop iden) iden fails the occurs check
+     */
+    test_cases.push(Box::new(|| {
+        let bytes = BitBuilder::program_preamble(4)
+            .iden()
+            .drop(1)
+            .iden()
+            .case(2, 1)
+            .witness_preamble(0)
+            .program_finished();
+        let cmr = Cmr::case(Cmr::drop(Cmr::iden()), Cmr::iden());
+        TestBuilder::comment("type_inference_occurs_check/occurs_check")
+            .description("Infinite type is inferred")
+            .raw_program(bytes)
+            .raw_cmr(cmr)
+            .expected_error(ScriptError::SimplicityTypeInferenceOccursCheck)
+            .finished()
+    }));
+
+    /*
+     * Infinite type is inferred only at the root of a deep program
+     *
+     * iden:                           X → X
+     * take^n iden:                    (..((X × B1) × B2) .. × Bn) → X
+     * drop (take^n iden):             A × C → X, where C = (..((X × B1) × B2) .. × Bn)
+     * case (drop (take^n iden)) iden: the right child is the shared iden, so X = B × C
+     *
+     * The cycle X = B × (..((X × B1) × B2) .. × Bn) has length n + 1.
+     * It forms only when the root is unified, after the n nested products of C are inferred.
+     */
+    fn deep_occurs_check_program(n: usize) -> (Vec<u8>, Cmr) {
+        let mut builder = BitBuilder::program_preamble(n + 3).iden();
+        let mut cmr = Cmr::iden();
+        for _ in 0..n {
+            builder = builder.take(1);
+            cmr = Cmr::take(cmr);
+        }
+        let bytes = builder
+            .drop(1)
+            .case(1, n + 2)
+            .witness_preamble(0)
+            .program_finished();
+        (bytes, Cmr::case(Cmr::drop(cmr), Cmr::iden()))
+    }
+
+    test_cases.push(Box::new(|| {
+        TestBuilder::comment("type_inference_occurs_check/deep_occurs_check")
+            .description("Infinite type is inferred only at the root of a deep program")
+            .raw_program_cmr(deep_occurs_check_program(100))
+            .expected_error(ScriptError::SimplicityTypeInferenceOccursCheck)
+            .finished()
+    }));
+
+    /*
+     * Source of program root is not unit
+     */
+    /// Program root has unit source type iff is_unit is true
+    ///
+    /// take unit: A × B → 1
+    fn root_source_type_program(is_unit: bool) -> (Vec<u8>, Cmr) {
+        let mut builder = BitBuilder::program_preamble(1 + usize::from(!is_unit)).unit();
+        let mut cmr = Cmr::unit();
+        if !is_unit {
+            builder = builder.take(1);
+            cmr = Cmr::take(cmr);
+        }
+        let bytes = builder.witness_preamble(0).program_finished();
+        (bytes, cmr)
+    }
+
+    test_cases.push(Box::new(|| {
+        TestBuilder::comment("type_inference_not_program/root_source_not_unit")
+            .description("Source of program root is not unit")
+            .raw_program_cmr(root_source_type_program(false))
+            .expected_error(ScriptError::SimplicityTypeInferenceNotProgram)
+            .finished()
+    }));
+
+    /*
+     * Source of program root is unit
+     */
+    test_cases.push(Box::new(|| {
+        TestBuilder::comment("type_inference_not_program/root_source_is_unit")
+            .description("Source of program root is unit")
+            .raw_program_cmr(root_source_type_program(true))
+            .expected_error(ScriptError::Ok)
+            .finished()
+    }));
+
+    /*
+     * Target of program root is not unit
+     */
+    /// Program root has unit target type iff is_unit is true
+    ///
+    /// pair unit unit: A → 1 × 1
+    fn root_target_type_program(is_unit: bool) -> (Vec<u8>, Cmr) {
+        let mut builder = BitBuilder::program_preamble(1 + usize::from(!is_unit)).unit();
+        let mut cmr = Cmr::unit();
+        if !is_unit {
+            builder = builder.pair(1, 1);
+            cmr = Cmr::pair(cmr, cmr);
+        }
+        let bytes = builder.witness_preamble(0).program_finished();
+        (bytes, cmr)
+    }
+
+    test_cases.push(Box::new(|| {
+        TestBuilder::comment("type_inference_not_program/root_target_no_unit")
+            .description("Target of program root is not unit")
+            .raw_program_cmr(root_target_type_program(false))
+            .expected_error(ScriptError::SimplicityTypeInferenceNotProgram)
+            .finished()
+    }));
+
+    /*
+     * Target of program root is unit
+     */
+    test_cases.push(Box::new(|| {
+        TestBuilder::comment("type_inference_not_program/root_target_is_unit")
+            .description("Target of program root is unit")
+            .raw_program_cmr(root_target_type_program(true))
+            .expected_error(ScriptError::Ok)
+            .finished()
+    }));
+
+    /*
+     * Neither source nor target of program root is unit
+     *
+     * The program swaps the components of its input.
+     * Both children share the same `iden` node, so both components have the same type:
+     * iden:                         A     → A
+     * drop iden:                    A × A → A
+     * take iden:                    A × A → A
+     * pair (drop iden) (take iden): A × A → A × A
+     *
+     * Every node is well-typed and the DAG decodes,
+     * but the root has type 1 × 1 → 1 × 1 after free type variables are set to unit.
+     */
+    test_cases.push(Box::new(|| {
+        let bytes = BitBuilder::program_preamble(4)
+            .iden()
+            .drop(1)
+            .take(2)
+            .pair(2, 1)
+            .witness_preamble(0)
+            .program_finished();
+        let cmr = Cmr::pair(Cmr::drop(Cmr::iden()), Cmr::take(Cmr::iden()));
+        TestBuilder::comment("type_inference_not_program/root_swap")
+            .description("Neither source nor target of program root is unit")
+            .raw_program(bytes)
+            .raw_cmr(cmr)
+            .expected_error(ScriptError::SimplicityTypeInferenceNotProgram)
+            .finished()
+    }));
+
+    super::generate(test_cases)
+}
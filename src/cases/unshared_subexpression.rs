@@ -0,0 +1,269 @@
+//! # Unshared subexpressions
+//!
+//! Programs in which the same subexpression occurs more than once.
+
+use simplicity::Cmr;
+
+use super::Deferred;
+use crate::bit_encoding::BitBuilder;
+use crate::json::{ScriptError, TestCase};
+use crate::test::TestBuilder;
+use crate::util;
+
+/// Return the default test cases of this category.
+pub fn cases() -> impl Iterator<Item = TestCase> {
+    let mut test_cases: Vec<Deferred> = Vec::new();
+
+    /*
+     * Two nodes have the same IMR
+     */
+    /// Program is maximally shared iff duplicate is false
+    fn duplicate_imr_program(duplicate: bool) -> (Vec<u8>, Cmr) {
+        let mut builder = BitBuilder::program_preamble(2 + usize::from(duplicate)).unit();
+        if duplicate {
+            builder = builder.unit().comp(2, 1);
+        } else {
+            builder = builder.comp(1, 1);
+        }
+        let bytes = builder.witness_preamble(0).program_finished();
+        let cmr = Cmr::comp(Cmr::unit(), Cmr::unit());
+
+        (bytes, cmr)
+    }
+
+    test_cases.push(Box::new(|| {
+        util::assert_same_cmr_different_bytes(
+            &duplicate_imr_program(true),
+            &duplicate_imr_program(false),
+        );
+        TestBuilder::comment("unshared_subexpression/duplicate_imr")
+            .description("Two nodes have the same IMR")
+            .raw_program_cmr(duplicate_imr_program(true))
+            .expected_error(ScriptError::SimplicityUnsharedSubexpression)
+            .finished()
+    }));
+
+    /*
+     * Each node has a unique IMR
+     */
+    test_cases.push(Box::new(|| {
+        TestBuilder::comment("unshared_subexpression/no_duplicate_imr")
+            .description("Each node has a unique IMR")
+            .raw_program_cmr(duplicate_imr_program(false))
+            .expected_error(ScriptError::Ok)
+            .finished()
+    }));
+
+    /*
+     * Two hidden nodes have the same payload
+     */
+    /// Program is maximally shared iff cmr1 == cmr2
+    fn duplicate_hidden_program(cmr1: Cmr, cmr2: Cmr) -> (Vec<u8>, Cmr) {
+        // FIXME: Use rust-simplicity encoder with sharing of hidden nodes disabled, once implemented
+        let bytes = BitBuilder::program_preamble(13)
+            // scribe ([1], [])
+            .unit() // 1 → 1
+            .injr(1) // 1 → 1 + 1
+            .pair(1, 2) // 1 → (1 + 1) × 1
+            // End scribe
+            .hidden(cmr1)
+            .unit() // 1 × 1 → 1
+            .case(2, 1) // (1 + 1) × 1 → 1
+            .comp(4, 1) // 1 → 1
+            .hidden(cmr2)
+            .iden() // 1 → 1
+            .take(1) // 1 × 1 → 1
+            // Forall cmr1 cmr2, IMR(assertr cmr1 unit) != IMR(assertr cmr2 (take iden))
+            .case(3, 1) // (1 + 1) × 1 → 1
+            .comp(9, 1) // 1 → 1
+            .comp(6, 1) // 1 → 1
+            .witness_preamble(0)
+            .program_finished();
+        let scribe = Cmr::pair(Cmr::injr(Cmr::unit()), Cmr::unit());
+        let cmr = Cmr::comp(
+            Cmr::comp(scribe, Cmr::case(cmr1, Cmr::unit())),
+            Cmr::comp(scribe, Cmr::case(cmr2, Cmr::take(Cmr::iden()))),
+        );
+
+        (bytes, cmr)
+    }
+
+    test_cases.push(Box::new(|| {
+        let same_cmr = Cmr::from_byte_array([0; 32]);
+        TestBuilder::comment("unshared_subexpression/duplicate_hidden")
+            .description("Two hidden nodes have the same payload")
+            .raw_program_cmr(duplicate_hidden_program(same_cmr, same_cmr))
+            // The library rejects repeated hidden nodes while decoding,
+            // so the CMR is checked by `no_duplicate_hidden` instead
+            .cmr_check(false)
+            .expected_error(ScriptError::SimplicityUnsharedSubexpression)
+            .finished()
+    }));
+
+    /*
+     * Two hidden nodes have different payload
+     *
+     * Test if `unshared_subexpression_program(cmr1, cmr2)` is maximally shared for cmr1 != cmr2
+     */
+    test_cases.push(Box::new(|| {
+        let same_cmr = Cmr::from_byte_array([0; 32]);
+        let different_cmr = Cmr::from_byte_array([1; 32]);
+        TestBuilder::comment("unshared_subexpression/no_duplicate_hidden")
+            .description("Two hidden nodes have different payload")
+            .raw_program_cmr(duplicate_hidden_program(same_cmr, different_cmr))
+            .expected_error(ScriptError::Ok)
+            .finished()
+    }));
+
+    /*
+     * Two assertions share one hidden node
+     *
+     * Like any other node, a hidden node may have several parents.
+     * Maximal sharing forbids two hidden nodes with the same payload,
+     * so two assertions that hide the same CMR must reference the same hidden node.
+     * This is the shared encoding of `duplicate_hidden_program(cmr, cmr)`.
+     */
+    fn shared_hidden_program(cmr: Cmr) -> (Vec<u8>, Cmr) {
+        let bytes = BitBuilder::program_preamble(12)
+            // scribe ([1], [])
+            .unit() // 1 → 1
+            .injr(1) // 1 → 1 + 1
+            .pair(1, 2) // 1 → (1 + 1) × 1
+            // End scribe
+            .hidden(cmr)
+            .unit() // 1 × 1 → 1
+            .case(2, 1) // (1 + 1) × 1 → 1
+            .comp(4, 1) // 1 → 1
+            .iden() // 1 → 1
+            .take(1) // 1 × 1 → 1
+            .case(6, 1) // (1 + 1) × 1 → 1, shares the hidden node
+            .comp(8, 1) // 1 → 1
+            .comp(5, 1) // 1 → 1
+            .witness_preamble(0)
+            .program_finished();
+        let scribe = Cmr::pair(Cmr::injr(Cmr::unit()), Cmr::unit());
+        let cmr = Cmr::comp(
+            Cmr::comp(scribe, Cmr::case(cmr, Cmr::unit())),
+            Cmr::comp(scribe, Cmr::case(cmr, Cmr::take(Cmr::iden()))),
+        );
+
+        (bytes, cmr)
+    }
+
+    test_cases.push(Box::new(|| {
+        let same_cmr = Cmr::from_byte_array([0; 32]);
+        TestBuilder::comment("ok/shared_hidden")
+            .description("Two assertions share one hidden node")
+            .raw_program_cmr(shared_hidden_program(same_cmr))
+            .expected_error(ScriptError::Ok)
+            .finished()
+    }));
+
+    /*
+     * Two encodings of the same program differ only in sharing
+     *
+     * unit:            1     → 1
+     * iden:            1     → 1
+     * comp unit iden:  1     → 1
+     * pair (..) (..):  1     → 1 × 1
+     * drop iden:       1 × 1 → 1
+     * comp (..) (..):  1     → 1
+     *
+     * Both encodings have the same CMR, so only one of them may be accepted.
+     * The canonical encoding shares the two copies of `comp unit iden`.
+     * The other encoding duplicates them, including their children.
+     */
+    /// Program is maximally shared iff shared is true
+    fn shared_pair_program(shared: bool) -> (Vec<u8>, Cmr) {
+        let bytes = match shared {
+            true => BitBuilder::program_preamble(6)
+                .unit()
+                .iden()
+                .comp(2, 1)
+                .pair(1, 1)
+                .drop(3)
+                .comp(2, 1),
+            false => BitBuilder::program_preamble(9)
+                .unit()
+                .iden()
+                .comp(2, 1)
+                .unit()
+                .iden()
+                .comp(2, 1)
+                .pair(4, 1)
+                .drop(3)
+                .comp(2, 1),
+        }
+        .witness_preamble(0)
+        .program_finished();
+        let unit_iden = Cmr::comp(Cmr::unit(), Cmr::iden());
+        let cmr = Cmr::comp(Cmr::pair(unit_iden, unit_iden), Cmr::drop(Cmr::iden()));
+
+        (bytes, cmr)
+    }
+
+    test_cases.push(Box::new(|| {
+        util::assert_same_cmr_different_bytes(
+            &shared_pair_program(false),
+            &shared_pair_program(true),
+        );
+        TestBuilder::comment("unshared_subexpression/same_cmr_unshared_pair")
+            .description("Two encodings of the same program differ only in sharing")
+            .raw_program_cmr(shared_pair_program(false))
+            .expected_error(ScriptError::SimplicityUnsharedSubexpression)
+            .finished()
+    }));
+
+    test_cases.push(Box::new(|| {
+        TestBuilder::comment("unshared_subexpression/same_cmr_shared_pair")
+            .description("Two encodings of the same program differ only in sharing")
+            .raw_program_cmr(shared_pair_program(true))
+            .expected_error(ScriptError::Ok)
+            .finished()
+    }));
+
+    /*
+     * Expensive program is unshared and costs more than MAX_BUDGET
+     *
+     * The ladder L_0 = iden, L_(k + 1) = comp L_k L_k costs about 2^(k + 1) × 100 mWU.
+     * L_24 costs less than MAX_BUDGET, but comp L_24 L_24 costs more.
+     * The program composes two separate copies of L_24 instead of sharing one,
+     * so it has two nodes for each IMR of the copies.
+     *
+     * Both the sharing check and the budget check fail.
+     * The validator checks for duplicate IMRs before it analyses the cost bounds,
+     * so SIMPLICITY_UNSHARED_SUBEXPRESSION fires first.
+     */
+    /// Return the program `comp L_depth L_depth` where both children are separate copies.
+    fn unshared_ladder_program(depth: usize) -> (Vec<u8>, Cmr) {
+        let copy_len = depth + 1;
+        let mut builder = BitBuilder::program_preamble(2 * copy_len + 1);
+        let mut cmr = Cmr::iden();
+
+        for _ in 0..2 {
+            builder = builder.iden();
+            for _ in 0..depth {
+                builder = builder.comp(1, 1);
+            }
+        }
+        for _ in 0..depth {
+            cmr = Cmr::comp(cmr, cmr);
+        }
+
+        let bytes = builder
+            .comp(copy_len + 1, 1)
+            .witness_preamble(0)
+            .program_finished();
+        (bytes, Cmr::comp(cmr, cmr))
+    }
+
+    test_cases.push(Box::new(|| {
+        TestBuilder::comment("unshared_subexpression/exceeds_max_budget")
+            .description("Expensive program is unshared and costs more than MAX_BUDGET")
+            .raw_program_cmr(unshared_ladder_program(24))
+            .expected_error(ScriptError::SimplicityUnsharedSubexpression)
+            .finished()
+    }));
+
+    super::generate(test_cases)
+}
@@ -0,0 +1,371 @@
+//! # Witness block
+//!
+//! Witness blocks that are too short, too long, or whose values have unusual types.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use simplicity::human_encoding::Forest;
+use simplicity::jet::{Elements, Jet};
+use simplicity::{Cmr, Value};
+
+use super::Deferred;
+use crate::bit_encoding::BitBuilder;
+use crate::json::{ScriptError, TestCase};
+use crate::test::TestBuilder;
+use crate::util::{self, Case};
+use crate::witness::WitnessBuilder;
+
+/// Return the default test cases of this category.
+pub fn cases() -> impl Iterator<Item = TestCase> {
+    let mut test_cases: Vec<Deferred> = Vec::new();
+
+    /*
+     * Witness value crosses a 64-bit word boundary
+     */
+    /// Program that consumes the witness `value` with the given `jet`.
+    ///
+    /// The jet fixes the type of the witness value:
+    /// - `jet_full_left_shift_64_1` takes 2^64 × 2 (65 bits)
+    /// - `jet_eq_64` takes 2^64 × 2^64 (128 bits)
+    /// - `jet_full_add_64` takes 2 × 2^64 × 2^64 (129 bits)
+    fn word_boundary_witness_program(jet: Elements, value: &Value) -> (Vec<u8>, Cmr) {
+        assert!(
+            matches!(
+                jet,
+                Elements::FullLeftShift64_1 | Elements::Eq64 | Elements::FullAdd64
+            ),
+            "Unsupported jet: {}",
+            jet
+        );
+        let bytes = BitBuilder::program_preamble(5)
+            .witness()
+            .named_jet(jet)
+            .unit()
+            .comp(2, 1)
+            .comp(4, 1)
+            .witness_block([value])
+            .program_finished();
+        let cmr = Cmr::comp(Cmr::witness(), Cmr::comp(jet.cmr(), Cmr::unit()));
+        (bytes, cmr)
+    }
+
+    for (jet, value) in [
+        (
+            Elements::FullLeftShift64_1,
+            Value::prod(Value::u64(u64::MAX), Value::u1(1)),
+        ),
+        (
+            Elements::Eq64,
+            Value::prod(Value::u64(u64::MAX), Value::u64(u64::MAX)),
+        ),
+        (
+            Elements::FullAdd64,
+            Value::prod(
+                Value::u1(1),
+                Value::prod(Value::u64(u64::MAX), Value::u64(u64::MAX)),
+            ),
+        ),
+    ] {
+        test_cases.push(Box::new(move || {
+            TestBuilder::comment(format!("ok/witness_value_{}_bits", value.len()))
+                .description("Witness value crosses a 64-bit word boundary")
+                .raw_program_cmr(word_boundary_witness_program(jet, &value))
+                .expected_error(ScriptError::Ok)
+                .finished()
+        }));
+    }
+
+    /*
+     * Witness value has complex type of non-zero bit size
+     *
+     * T_0     = 2^8
+     * T_(k+1) = T_k × T_k
+     *
+     * U_0     := comp jet_complement_8 unit                    : T_0     → 1
+     * U_(k+1) := comp (pair (take U_k) (drop U_k)) (drop iden)  : T_(k+1) → 1
+     * main    := comp witness U_depth                          : 1       → 1
+     *
+     * The witness value is a perfect binary tree of 2^depth bytes 0, 1, 2, ...
+     * For depth 5, the witness type T_5 has a bit size of 32 × 8 = 256 bits.
+     */
+    /// Program that unpacks a witness of type T_depth, for 0 < depth.
+    fn nested_witness_program(depth: usize, value: &Value) -> (Vec<u8>, Cmr) {
+        assert!(0 < depth, "Depth must be positive");
+        // Nodes: witness, U_0 (3), U_1 to U_depth (4 each), `drop iden` (2), root
+        let mut builder = BitBuilder::program_preamble(1 + 3 + 4 * depth + 2 + 1)
+            .witness()
+            .named_jet(Elements::Complement8)
+            .unit()
+            .comp(2, 1);
+        let mut unpack_cmr = Cmr::comp(Elements::Complement8.cmr(), Cmr::unit());
+        let drop_iden_cmr = Cmr::drop(Cmr::iden());
+        // Index of U_k
+        let mut unpack = 3;
+        // Index of `drop iden`, which is shared across all levels
+        let mut drop_iden = None;
+
+        for _ in 0..depth {
+            let take = unpack + 1;
+            builder = builder
+                .take(take - unpack)
+                .drop(take + 1 - unpack)
+                .pair(2, 1);
+            let pair = take + 2;
+            let next = match drop_iden {
+                None => {
+                    builder = builder.iden().drop(1).comp(3, 1);
+                    drop_iden = Some(pair + 2);
+                    pair + 3
+                }
+                Some(drop_iden) => {
+                    builder = builder.comp(1, pair + 1 - drop_iden);
+                    pair + 1
+                }
+            };
+            unpack_cmr = Cmr::comp(
+                Cmr::pair(Cmr::take(unpack_cmr), Cmr::drop(unpack_cmr)),
+                drop_iden_cmr,
+            );
+            unpack = next;
+        }
+
+        let bytes = builder
+            .comp(unpack + 1, 1)
+            .witness_block([value])
+            .program_finished();
+        let cmr = Cmr::comp(Cmr::witness(), unpack_cmr);
+        (bytes, cmr)
+    }
+
+    test_cases.push(Box::new(|| {
+        let depth = 5;
+        let mut values: Vec<Arc<Value>> = (0..1u8 << depth).map(Value::u8).collect();
+        while 1 < values.len() {
+            values = values
+                .chunks(2)
+                .map(|pair| Value::prod(pair[0].clone(), pair[1].clone()))
+                .collect();
+        }
+        let value = values.pop().unwrap();
+        assert_eq!(256, value.len());
+        TestBuilder::comment("ok/complex_witness_type_256_bits")
+            .description("Witness value has complex type of non-zero bit size")
+            .raw_program_cmr(nested_witness_program(depth, &value))
+            .expected_error(ScriptError::Ok)
+            .finished()
+    }));
+
+    /*
+     * Parse next witness value, but bitstring is EOF
+     */
+    test_cases.push(Box::new(|| {
+        let bytes = BitBuilder::program_preamble(5)
+            .witness() // 1 → (1 + 1) * 1 means bit size = 1
+            .unit()
+            .take(1)
+            .case(1, 1)
+            .comp(4, 1)
+            .witness_preamble(0) // bitstring: []
+            .parser_stops_here();
+        let cmr = Cmr::comp(
+            Cmr::witness(),
+            Cmr::case(Cmr::take(Cmr::unit()), Cmr::take(Cmr::unit())),
+        );
+        TestBuilder::comment("witness_eof/next_value")
+            .description("Parse next witness value, but bitstring is EOF")
+            .raw_program(bytes)
+            .raw_cmr(cmr)
+            .expected_error(ScriptError::SimplicityWitnessEof)
+            .finished()
+    }));
+
+    /*
+     * Parse next bit of witness value, but bitstring is EOF
+     */
+    test_cases.push(Box::new(|| {
+        let bytes = BitBuilder::program_preamble(6)
+            .witness() // 1 → ((1 + 1) + (1 + 1)) × 1 means bit size = 2
+            .unit()
+            .take(1)
+            .case(1, 1)
+            .case(1, 1)
+            .comp(5, 1)
+            .witness_preamble(1) // bitstring: [1]
+            .witness_value(&Value::u1(1))
+            .parser_stops_here();
+        let cmr = Cmr::comp(
+            Cmr::witness(),
+            Cmr::case(
+                Cmr::case(Cmr::take(Cmr::unit()), Cmr::take(Cmr::unit())),
+                Cmr::case(Cmr::take(Cmr::unit()), Cmr::take(Cmr::unit())),
+            ),
+        );
+        TestBuilder::comment("witness_eof/next_bit")
+            .description("Parse next bit of witness value, but bitstring is EOF")
+            .raw_program(bytes)
+            .raw_cmr(cmr)
+            .expected_error(ScriptError::SimplicityWitnessEof)
+            .finished()
+    }));
+
+    /*
+     * Witness value is cut off at a 64-bit word boundary
+     *
+     * jet_eq_64 takes 2^64 × 2^64, so the witness value has 128 bits.
+     * The witness block contains only the first 64 bits.
+     * The parser reads the 65th bit, which is past the end of the witness block.
+     */
+    test_cases.push(Box::new(|| {
+        let bytes = BitBuilder::program_preamble(5)
+            .witness()
+            .named_jet(Elements::Eq64)
+            .unit()
+            .comp(2, 1)
+            .comp(4, 1)
+            .witness_block([Value::u64(u64::MAX).as_ref()]) // bitstring: 64 × [1]
+            .parser_stops_here();
+        let cmr = Cmr::comp(Cmr::witness(), Cmr::comp(Elements::Eq64.cmr(), Cmr::unit()));
+        TestBuilder::comment("witness_eof/word_boundary")
+            .description("Witness value is cut off at a 64-bit word boundary")
+            .raw_program(bytes)
+            .raw_cmr(cmr)
+            .expected_error(ScriptError::SimplicityWitnessEof)
+            .finished()
+    }));
+
+    /*
+     * Witness value has a sum type
+     *
+     * wit: 1 → 2 = 1 + 1
+     *
+     * The witness value is encoded as its tag bit:
+     * [0] is the left unit and [1] is the right unit.
+     * The program asserts that the witness value is on the given side.
+     */
+    /// Program that succeeds iff the witness value is left (`Case::Left`) or right (`Case::Right`).
+    fn sum_witness_program(case: Case) -> String {
+        format!(
+            "
+            wit := witness : 1 -> 2
+            main := comp (pair wit unit) ({} {} {})
+        ",
+            case,
+            case.left_child("unit"),
+            case.right_child("unit")
+        )
+    }
+
+    for (case, bit) in [(Case::Left, 0), (Case::Right, 1)] {
+        test_cases.push(Box::new(move || {
+            let witness = WitnessBuilder::new().value("wit", Value::u1(bit)).to_map();
+            TestBuilder::comment(format!("ok/sum_witness_{bit}"))
+                .description("Witness value has a sum type")
+                .human_encoding(&sum_witness_program(case), &witness)
+                .expected_error(ScriptError::Ok)
+                .finished()
+        }));
+    }
+
+    /*
+     * Parse tag bit of sum-typed witness value, but bitstring is EOF
+     */
+    test_cases.push(Box::new(|| {
+        let forest = Forest::parse(&sum_witness_program(Case::Left)).unwrap();
+        let program = forest.to_witness_node(&HashMap::new()).expect("has main");
+        let bytes = simplicity::write_to_vec(|w| util::encode_program_empty_witness(&program, w));
+        TestBuilder::comment("witness_eof/sum_witness_tag")
+            .description("Parse tag bit of sum-typed witness value, but bitstring is EOF")
+            .raw_program(bytes)
+            .raw_cmr(program.cmr())
+            .expected_error(ScriptError::SimplicityWitnessEof)
+            .finished()
+    }));
+
+    /*
+     * Witness node consumes a single bit
+     *
+     * witness:          1 → 2
+     * jet_complement_1: 2 → 2
+     * unit:             2 → 1
+     *
+     * The witness values [0] and [1] are both accepted.
+     * The empty witness block runs out of bits.
+     */
+    /// Program that consumes the given witness `value` with a 1-bit `witness` node.
+    ///
+    /// The witness block is empty if there is no value.
+    fn witness_bit_program(value: Option<&Value>) -> (Vec<u8>, Cmr) {
+        let bytes = BitBuilder::program_preamble(5)
+            .witness()
+            .named_jet(Elements::Complement1)
+            .comp(2, 1)
+            .unit()
+            .comp(2, 1)
+            .witness_block(value)
+            .program_finished();
+        let cmr = Cmr::comp(
+            Cmr::comp(Cmr::witness(), Elements::Complement1.cmr()),
+            Cmr::unit(),
+        );
+        (bytes, cmr)
+    }
+
+    for bit in [0, 1] {
+        test_cases.push(Box::new(move || {
+            TestBuilder::comment(format!("ok/witness_bit_{bit}"))
+                .description("Witness node consumes a single bit")
+                .raw_program_cmr(witness_bit_program(Some(&Value::u1(bit))))
+                .expected_error(ScriptError::Ok)
+                .finished()
+        }));
+    }
+
+    test_cases.push(Box::new(|| {
+        TestBuilder::comment("witness_eof/witness_bit")
+            .description("Witness node consumes a single bit, but bitstring is EOF")
+            .raw_program_cmr(witness_bit_program(None))
+            .expected_error(ScriptError::SimplicityWitnessEof)
+            .finished()
+    }));
+
+    /*
+     * Witness block declared too long
+     */
+    /// Program causes SIMPLICITY_WITNESS_TRAILING_BITS iff trailing_bit is true
+    fn trailing_bits_program(trailing_bit: bool) -> (Vec<u8>, Cmr) {
+        let mut builder = BitBuilder::program_preamble(3)
+            .witness()
+            .unit()
+            .comp(2, 1)
+            .witness_preamble(usize::from(trailing_bit));
+        if trailing_bit {
+            builder = builder.witness_value(&Value::u1(1));
+        }
+        let bytes = builder.program_finished();
+        let cmr = Cmr::comp(Cmr::witness(), Cmr::unit());
+
+        (bytes, cmr)
+    }
+
+    test_cases.push(Box::new(|| {
+        TestBuilder::comment("witness_trailing_bits/witness_too_long")
+            .description("Witness block declared too long")
+            .raw_program_cmr(trailing_bits_program(true))
+            .expected_error(ScriptError::SimplicityWitnessUnusedBits)
+            .finished()
+    }));
+
+    /*
+     * Witness block has correct length
+     */
+    test_cases.push(Box::new(|| {
+        TestBuilder::comment("witness_trailing_bits/witness_length_ok")
+            .description("Witness block has correct length")
+            .raw_program_cmr(trailing_bits_program(false))
+            .expected_error(ScriptError::Ok)
+            .finished()
+    }));
+
+    super::generate(test_cases)
+}
@@ -0,0 +1,410 @@
+//! # Witness stack
+//!
+//! Spends whose Taproot witness stack has the wrong shape, and the verdict of the outer interpreter.
+
+use std::collections::HashMap;
+
+use elements_miniscript::elements::opcodes::all::{
+    OP_2DROP, OP_DROP, OP_PUSHBYTES_0, OP_PUSHNUM_1, OP_RETURN,
+};
+use elements_miniscript::elements::secp256k1_zkp;
+use elements_miniscript::elements::taproot::LeafVersion;
+
+use super::Deferred;
+use crate::json::{ScriptError, TestCase};
+use crate::test::TestBuilder;
+
+/// Return the default test cases of this category.
+pub fn cases() -> impl Iterator<Item = TestCase> {
+    let mut test_cases: Vec<Deferred> = Vec::new();
+
+    /*
+     * `unit` is an ANYONECANSPEND, but the witness stack has an extra empty element
+     *
+     * The program is trivial and would succeed, but the witness stack is
+     * [program, empty element, script, control block]. Simplicity expects exactly one
+     * script input, so the spend fails before the program is decoded.
+     * An empty element counts like any other.
+     */
+    test_cases.push(Box::new(|| {
+        let s = "main := unit";
+        TestBuilder::comment("wrong_length/unit_extra_empty_element")
+            .description(
+                "`unit` is an ANYONECANSPEND, but the witness stack has an extra empty element",
+            )
+            .human_encoding(s, &HashMap::new())
+            .extra_script_input(vec![])
+            .expected_error(ScriptError::SimplicityWrongLength)
+            .finished()
+    }));
+
+    /*
+     * Taproot witness stack is longer than 3 elements
+     */
+    test_cases.push(Box::new(|| {
+        let s = "main := unit";
+        TestBuilder::comment("wrong_length/multiple_script_inputs")
+            .description("Taproot witness stack is longer than 3 elements")
+            .human_encoding(s, &HashMap::new())
+            .extra_script_input(vec![0x00])
+            .expected_error(ScriptError::SimplicityWrongLength)
+            .finished()
+    }));
+
+    /*
+     * Taproot witness stack is shorter than 3 elements
+     *
+     * Taproot enforces at least two witness stack elements:
+     * witness script + control block
+     * This is checked by the taproot test suite
+     *
+     * We check a witness stack of exactly two elements
+     */
+    test_cases.push(Box::new(|| {
+        let s = "main := unit";
+        TestBuilder::comment("wrong_length/no_script_inputs")
+            .description("Taproot witness stack is shorter than 3 elements")
+            .human_encoding(s, &HashMap::new())
+            .skip_script_inputs()
+            .expected_error(ScriptError::SimplicityWrongLength)
+            .finished()
+    }));
+
+    /*
+     * Taproot witness stack has no control block
+     *
+     * The witness stack consists of program and script.
+     * Taproot takes the last element, which is the 32-byte script, as control block.
+     * This is not a valid control block size (33 + 32m bytes).
+     */
+    test_cases.push(Box::new(|| {
+        let s = "main := unit";
+        TestBuilder::comment("taproot_wrong_control_size/no_control_block")
+            .description("Taproot witness stack has no control block")
+            .human_encoding(s, &HashMap::new())
+            .skip_control_block()
+            .expected_error(ScriptError::TaprootWrongControlSize)
+            .finished()
+    }));
+
+    /*
+     * Control block is one byte too short or too long
+     *
+     * A valid control block has 33 + 32m bytes.
+     * Taproot checks the size of the control block before Simplicity runs,
+     * so the program is never decoded.
+     * The unmodified control block is the negative control.
+     */
+    /// Remove the last byte of the control block.
+    fn truncate(mut control_block: Vec<u8>) -> Vec<u8> {
+        control_block.pop();
+        control_block
+    }
+    /// Append a zero byte to the control block.
+    fn extend(mut control_block: Vec<u8>) -> Vec<u8> {
+        control_block.push(0x00);
+        control_block
+    }
+    /// Keep the control block as it is.
+    fn keep(control_block: Vec<u8>) -> Vec<u8> {
+        control_block
+    }
+
+    for (mangle, comment, error) in [
+        (
+            truncate as fn(Vec<u8>) -> Vec<u8>,
+            "taproot_wrong_control_size/control_block_truncated",
+            ScriptError::TaprootWrongControlSize,
+        ),
+        (
+            extend,
+            "taproot_wrong_control_size/control_block_extended",
+            ScriptError::TaprootWrongControlSize,
+        ),
+        (keep, "ok/control_block_unmodified", ScriptError::Ok),
+    ] {
+        test_cases.push(Box::new(move || {
+            let s = "main := unit";
+            TestBuilder::comment(comment)
+                .description("Control block is one byte too short or too long")
+                .human_encoding(s, &HashMap::new())
+                .mangle_control_block(mangle)
+                .expected_error(error)
+                .finished()
+        }));
+    }
+
+    /*
+     * Simplicity leaf has sibling leaves in the tap tree
+     *
+     * The siblings are tapscript leaves at depths n, n - 1, ..., 1, so the Simplicity leaf
+     * is at depth n and its control block carries a Merkle path of n hashes.
+     */
+    for n_siblings in 1..=3u8 {
+        test_cases.push(Box::new(move || {
+            let s = "main := unit";
+            let siblings = (1..=n_siblings)
+                .rev()
+                .map(|depth| (depth, vec![OP_PUSHNUM_1.into_u8(); usize::from(depth)]))
+                .collect();
+            let test_case = TestBuilder::comment(format!("ok/unit_{n_siblings}_sibling_leaves"))
+                .description("Simplicity leaf has sibling leaves in the tap tree")
+                .human_encoding(s, &HashMap::new())
+                .sibling_leaves(siblings)
+                .expected_error(ScriptError::Ok)
+                .finished();
+            let control_block = test_case.success.as_ref().unwrap().witness.last().unwrap();
+            assert_eq!(33 + 32 * usize::from(n_siblings), control_block.0.len());
+            test_case
+        }));
+    }
+
+    /*
+     * Control block has the wrong internal key
+     *
+     * The output key is the unspendable key tweaked with the tap tree that contains the CMR.
+     * The control block carries the key of the secret key 0x01..01 instead.
+     * Parity and merkle path are unchanged, so the recomputed output key differs
+     * only because of the internal key.
+     */
+    test_cases.push(Box::new(|| {
+        let secp = secp256k1_zkp::Secp256k1::new();
+        let (wrong_internal_key, _) = secp256k1_zkp::Keypair::from_seckey_slice(&secp, &[0x01; 32])
+            .expect("valid secret key")
+            .x_only_public_key();
+        let s = "main := unit";
+        TestBuilder::comment("witness_program_mismatch/wrong_internal_key")
+            .description("Control block has the wrong internal key")
+            .human_encoding(s, &HashMap::new())
+            .control_block_internal_key(wrong_internal_key)
+            .expected_error(ScriptError::WitnessProgramMismatch)
+            .finished()
+    }));
+
+    /*
+     * Taproot witness stack is exactly 3 elements
+     */
+    test_cases.push(Box::new(|| {
+        let s = "main := unit";
+        TestBuilder::comment("wrong_length/one_script_input")
+            .description("Taproot witness stack is exactly 3 elements")
+            .human_encoding(s, &HashMap::new())
+            .expected_error(ScriptError::Ok)
+            .finished()
+    }));
+
+    /*
+     * CMR is shorter than 32 bytes
+     */
+    test_cases.push(Box::new(|| {
+        let s = "main := unit";
+        TestBuilder::comment("wrong_length/too_short_cmr")
+            .description("CMR is shorter than 32 bytes")
+            .human_encoding(s, &HashMap::new())
+            .raw_cmr([0; 31])
+            .allow_cmr_mismatch()
+            .expected_error(ScriptError::SimplicityWrongLength)
+            .finished()
+    }));
+
+    /*
+     * CMR is longer than 32 bytes
+     */
+    test_cases.push(Box::new(|| {
+        let s = "main := unit";
+        TestBuilder::comment("wrong_length/too_long_cmr")
+            .description("CMR is longer than 32 bytes")
+            .human_encoding(s, &HashMap::new())
+            .raw_cmr([0; 33])
+            .allow_cmr_mismatch()
+            .expected_error(ScriptError::SimplicityWrongLength)
+            .finished()
+    }));
+
+    /*
+     * CMR is exactly 32 bytes
+     */
+    test_cases.push(Box::new(|| {
+        let s = "main := unit";
+        TestBuilder::comment("wrong_length/good_cmr")
+            .description("CMR is exactly 32 bytes")
+            .human_encoding(s, &HashMap::new())
+            .expected_error(ScriptError::Ok)
+            .finished()
+    }));
+
+    /*
+     * Simplicity verdict is the outer verdict
+     *
+     * Tapscript requires the final stack to consist of exactly one true element.
+     * Simplicity leaves are not evaluated as script, so there is no final stack:
+     * When the Simplicity program succeeds, the spend returns Ok,
+     * without EVAL_FALSE or CLEANSTACK.
+     */
+    test_cases.push(Box::new(|| {
+        let s = "main := unit";
+        TestBuilder::comment("outer_verdict/simplicity_ok")
+            .description("Simplicity verdict is the outer verdict")
+            .human_encoding(s, &HashMap::new())
+            .expected_error(ScriptError::Ok)
+            .finished()
+    }));
+
+    /*
+     * Negative control: The same witness stack evaluated as tapscript
+     *
+     * The leaf `OP_1` leaves the program bytes below `1` on the final stack
+     */
+    test_cases.push(Box::new(|| {
+        let s = "main := unit";
+        TestBuilder::comment("outer_verdict/tapscript_cleanstack")
+            .description("Negative control: The same witness stack evaluated as tapscript")
+            .human_encoding(s, &HashMap::new())
+            .reset_cost()
+            .raw_cmr([OP_PUSHNUM_1.into_u8()])
+            .allow_cmr_mismatch()
+            .leaf_version(LeafVersion::default())
+            .expected_error(ScriptError::Cleanstack)
+            .finished()
+    }));
+
+    /*
+     * Negative control: Tapscript leaves a false element on the final stack
+     */
+    test_cases.push(Box::new(|| {
+        TestBuilder::comment("outer_verdict/tapscript_eval_false")
+            .description("Negative control: Tapscript leaves a false element on the final stack")
+            .raw_program(vec![])
+            .skip_script_inputs()
+            .raw_cmr([OP_PUSHBYTES_0.into_u8()])
+            .leaf_version(LeafVersion::default())
+            .expected_error(ScriptError::EvalFalse)
+            .finished()
+    }));
+
+    /*
+     * Same program under an unknown leaf version
+     *
+     * The control block selects a leaf of version 0xbc, which is neither Simplicity (0xbe)
+     * nor tapscript (0xc4). Unknown leaf versions succeed unconditionally,
+     * unless DISCOURAGE_UPGRADABLE_TAPROOT_VERSION is set, which the tests do not set.
+     * The program is not decoded, so a program that fails under Simplicity succeeds, too.
+     * The same programs under the Simplicity leaf version are the controls.
+     */
+    for (name, s, simplicity_error) in [
+        ("unit", "main := unit", ScriptError::Ok),
+        (
+            "jet_fails",
+            "main := comp (const 0b0) jet_verify",
+            ScriptError::SimplicityExecJet,
+        ),
+    ] {
+        test_cases.push(Box::new(move || {
+            let unknown_version = LeafVersion::from_u8(0xbc).expect("valid leaf version");
+            TestBuilder::comment(format!("ok/unknown_leaf_version_{name}"))
+                .description("Same program under an unknown leaf version")
+                .human_encoding(s, &HashMap::new())
+                .leaf_version(unknown_version)
+                .expected_error(ScriptError::Ok)
+                .finished()
+        }));
+
+        test_cases.push(Box::new(move || {
+            let comment = match simplicity_error {
+                ScriptError::Ok => format!("ok/simplicity_leaf_version_{name}"),
+                _ => format!("exec_jet/simplicity_leaf_version_{name}"),
+            };
+            TestBuilder::comment(comment)
+                .description("Same program under the Simplicity leaf version")
+                .human_encoding(s, &HashMap::new())
+                .expected_error(simplicity_error)
+                .finished()
+        }));
+    }
+
+    /*
+     * Negative control: Tapscript executes `OP_RETURN`
+     */
+    test_cases.push(Box::new(|| {
+        TestBuilder::comment("outer_verdict/tapscript_op_return")
+            .description("Negative control: Tapscript executes `OP_RETURN`")
+            .raw_program(vec![])
+            .skip_script_inputs()
+            .raw_cmr([OP_RETURN.into_u8()])
+            .leaf_version(LeafVersion::default())
+            .expected_error(ScriptError::OpReturn)
+            .finished()
+    }));
+
+    /*
+     * Key path spend of an output whose tap tree has a Simplicity leaf
+     *
+     * The internal key is the key of the secret key 0x01..01 instead of the unspendable key.
+     * The witness stack is a single BIP 341 signature, so Simplicity is bypassed.
+     * The leaf program always fails, which would raise SIMPLICITY_EXEC_JET on the script path.
+     */
+    test_cases.push(Box::new(|| {
+        let secret_key =
+            secp256k1_zkp::SecretKey::from_slice(&[0x01; 32]).expect("valid secret key");
+        let s = "main := comp (const 0b0) jet_verify";
+        TestBuilder::comment("outer_verdict/key_path_spend")
+            .description("Key path spend of an output whose tap tree has a Simplicity leaf")
+            .human_encoding(s, &HashMap::new())
+            .key_path_spend(secret_key)
+            .expected_error(ScriptError::Ok)
+            .finished()
+    }));
+
+    /*
+     * Witness stack size
+     *
+     * Tapscript allows at most 1000 elements on the initial stack (MAX_STACK_SIZE).
+     * The witness stack consists of the "program" bytes 0x01
+     * followed by copies of the element 0x01.
+     *
+     * The leaf drops all elements except one, which leaves a true element on the final stack.
+     */
+    /// Tapscript leaf that drops `n_elements - 1` stack elements.
+    fn drop_elements_script(n_elements: usize) -> Vec<u8> {
+        let mut script = vec![OP_2DROP.into_u8(); (n_elements - 1) / 2];
+        if (n_elements - 1) % 2 == 1 {
+            script.push(OP_DROP.into_u8());
+        }
+        script
+    }
+
+    const MAX_STACK_SIZE: usize = 1000;
+    for n_elements in [MAX_STACK_SIZE, MAX_STACK_SIZE + 1] {
+        test_cases.push(Box::new(move || {
+            let (comment, error) = if n_elements <= MAX_STACK_SIZE {
+                ("stack_size/tapscript_max_elements", ScriptError::Ok)
+            } else {
+                ("stack_size/tapscript_exceeds_max", ScriptError::StackSize)
+            };
+            TestBuilder::comment(comment)
+                .description("Witness stack size")
+                .raw_program(vec![0x01])
+                .repeat_witness_element(vec![0x01], n_elements - 1)
+                .raw_cmr(drop_elements_script(MAX_STACK_SIZE))
+                .leaf_version(LeafVersion::default())
+                .expected_error(error)
+                .finished()
+        }));
+    }
+
+    /*
+     * Simplicity leaves never reach MAX_STACK_SIZE:
+     * More than one script input is rejected first
+     */
+    test_cases.push(Box::new(|| {
+        let s = "main := unit";
+        TestBuilder::comment("stack_size/simplicity_exceeds_max")
+            .description("Simplicity leaves never reach MAX_STACK_SIZE")
+            .human_encoding(s, &HashMap::new())
+            .repeat_witness_element(vec![0x01], MAX_STACK_SIZE)
+            .expected_error(ScriptError::SimplicityWrongLength)
+            .finished()
+    }));
+
+    super::generate(test_cases)
+}
@@ -0,0 +1,281 @@
+use std::io;
+
+use crate::json::{ScriptError, TestCase};
+
+/// How a Simplicity script error is covered by the test cases.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Coverage {
+    /// Comment of the minimal test case of a category that fails with the error.
+    Case(&'static str),
+    /// No transaction makes Elements raise the error, by design.
+    /// Each entry documents why.
+    Unreachable,
+}
+
+/// Coverage of each Simplicity script error.
+///
+/// The coverage is derived from the categories instead of duplicating their programs.
+/// The binary checks that each registered test case is generated.
+const COVERAGE: &[(ScriptError, Coverage)] = &[
+    (
+        ScriptError::SimplicityWrongLength,
+        Coverage::Case("wrong_length/no_script_inputs"),
+    ),
+    (
+        ScriptError::SimplicityBitstreamEof,
+        Coverage::Case("bitstream_eof/empty_program"),
+    ),
+    // Unreachable: The C implementation has no occurrence of this error, see `errors.md`.
+    (
+        ScriptError::SimplicityNotYetImplemented,
+        Coverage::Unreachable,
+    ),
+    (
+        ScriptError::SimplicityDataOutOfRange,
+        Coverage::Case("data_out_of_range/relative_child_index_too_large"),
+    ),
+    (
+        ScriptError::SimplicityDataOutOfOrder,
+        Coverage::Case("data_out_of_order/not_in_canonical_order"),
+    ),
+    (
+        ScriptError::SimplicityFailCode,
+        Coverage::Case("fail_code/fail_node"),
+    ),
+    (
+        ScriptError::SimplicityStopCode,
+        Coverage::Case("stop_code/stop_code"),
+    ),
+    (
+        ScriptError::SimplicityHidden,
+        Coverage::Case("hidden/comp_left_hidden"),
+    ),
+    (
+        ScriptError::SimplicityBitstreamUnusedBytes,
+        Coverage::Case("bitstream_trailing_bytes/trailing_bytes"),
+    ),
+    (
+        ScriptError::SimplicityBitstreamUnusedBits,
+        Coverage::Case("bitstream_illegal_padding/illegal_padding"),
+    ),
+    (
+        ScriptError::SimplicityTypeInferenceUnification,
+        Coverage::Case("type_inference_unification/comp_unify_left_target_right_source"),
+    ),
+    (
+        ScriptError::SimplicityTypeInferenceOccursCheck,
+        Coverage::Case("type_inference_occurs_check/occurs_check"),
+    ),
+    (
+        ScriptError::SimplicityTypeInferenceNotProgram,
+        Coverage::Case("type_inference_not_program/root_source_not_unit"),
+    ),
+    (
+        ScriptError::SimplicityWitnessEof,
+        Coverage::Case("witness_eof/witness_bit"),
+    ),
+    (
+        ScriptError::SimplicityWitnessUnusedBits,
+        Coverage::Case("witness_trailing_bits/witness_too_long"),
+    ),
+    (
+        ScriptError::SimplicityUnsharedSubexpression,
+        Coverage::Case("unshared_subexpression/duplicate_imr"),
+    ),
+    (ScriptError::SimplicityCmr, Coverage::Case("cmr/mismatch")),
+    // Unreachable from a transaction: Elements calls `simplicity_elements_execSimplicity`
+    // with `amr = NULL`, so the AMR is never compared. Tampering with the witness block
+    // or the types changes the AMR, but nothing is committed to that could be mismatched.
+    (ScriptError::SimplicityAmr, Coverage::Unreachable),
+    (
+        ScriptError::SimplicityExecBudget,
+        Coverage::Case("exec_budget/insufficient_padding"),
+    ),
+    (
+        ScriptError::SimplicityExecMemory,
+        Coverage::Case("exec_memory/memory_usage_exceeds_max_cells"),
+    ),
+    (
+        ScriptError::SimplicityExecJet,
+        Coverage::Case("exec_jet/jet_verify_fails"),
+    ),
+    (
+        ScriptError::SimplicityExecAssert,
+        Coverage::Case("antidos/some_unexecuted_assertl_go_right"),
+    ),
+    (
+        ScriptError::SimplicityAntidos,
+        Coverage::Case("antidos/some_unexecuted_case_go_left"),
+    ),
+    (
+        ScriptError::SimplicityHiddenRoot,
+        Coverage::Case("hidden_root/hidden_root"),
+    ),
+];
+
+/// Return the coverage of the given script error.
+///
+/// Return `None` if the error is not a Simplicity script error.
+pub fn coverage(error: ScriptError) -> Option<Coverage> {
+    COVERAGE
+        .iter()
+        .find(|(registered, _)| *registered == error)
+        .map(|(_, coverage)| *coverage)
+}
+
+/// Return the comment of the minimal test case that fails with the given script error.
+///
+/// Return `None` if no test case is registered for the error.
+pub fn covering_case(error: ScriptError) -> Option<&'static str> {
+    match coverage(error)? {
+        Coverage::Case(comment) => Some(comment),
+        Coverage::Unreachable => None,
+    }
+}
+
+/// Count the test cases of each script error, in the order of [`ScriptError::ALL`].
+///
+/// A success arm counts as `Ok`, a failure arm counts as its error.
+/// Test cases with both arms are counted once for each arm.
+pub fn error_counts(test_cases: &[TestCase]) -> Vec<(ScriptError, usize)> {
+    let mut counts = zero_error_counts();
+    for test_case in test_cases {
+        count_errors(&mut counts, test_case);
+    }
+    counts
+}
+
+/// Return a count of zero for each script error, in the order of [`ScriptError::ALL`].
+pub fn zero_error_counts() -> Vec<(ScriptError, usize)> {
+    ScriptError::ALL.iter().map(|error| (*error, 0)).collect()
+}
+
+/// Add the arms of the given test case to the counts, as [`error_counts`] does.
+pub fn count_errors(counts: &mut [(ScriptError, usize)], test_case: &TestCase) {
+    let success = test_case.success.as_ref().map(|_| ScriptError::Ok);
+    let failure = test_case
+        .failure
+        .as_ref()
+        .map(|failure| failure.error.unwrap_or(ScriptError::Ok));
+    for error in success.into_iter().chain(failure) {
+        let (_, count) = counts
+            .iter_mut()
+            .find(|(counted, _)| *counted == error)
+            .expect("ScriptError::ALL lists every error");
+        *count += 1;
+    }
+}
+
+/// Return the Simplicity script errors without a test case.
+///
+/// Every Simplicity error must have a test case, except those that are registered
+/// as [`Coverage::Unreachable`]. Each of these documents why it cannot be covered.
+pub fn uncovered_errors(counts: &[(ScriptError, usize)]) -> Vec<ScriptError> {
+    counts
+        .iter()
+        .filter(|(error, count)| {
+            *count == 0
+                && is_simplicity_error(*error)
+                && coverage(*error) != Some(Coverage::Unreachable)
+        })
+        .map(|(error, _)| *error)
+        .collect()
+}
+
+/// Return the Simplicity script errors whose registered test case is not among the given comments.
+pub fn missing_covering_cases<'a, I>(comments: I) -> Vec<ScriptError>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let comments: Vec<&str> = comments.into_iter().collect();
+    COVERAGE
+        .iter()
+        .filter_map(|(error, coverage)| match coverage {
+            Coverage::Case(comment) if !comments.contains(comment) => Some(*error),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Return whether the given script error is raised by Simplicity.
+fn is_simplicity_error(error: ScriptError) -> bool {
+    error.to_string().starts_with("SIMPLICITY_")
+}
+
+/// Write the number of test cases of each script error that occurs at least once.
+///
+/// Each error is written on a line of its own: count and error, separated by a tab.
+pub fn write_error_counts<W: io::Write>(
+    counts: &[(ScriptError, usize)],
+    mut w: W,
+) -> io::Result<()> {
+    for (error, count) in counts {
+        if 0 < *count {
+            writeln!(w, "{count:>5}\t{error}")?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::TestBuilder;
+    use std::collections::HashMap;
+
+    #[test]
+    fn every_simplicity_error_is_registered() {
+        let simplicity_errors: Vec<ScriptError> = ScriptError::ALL
+            .iter()
+            .filter(|error| is_simplicity_error(**error))
+            .copied()
+            .collect();
+        let registered: Vec<ScriptError> = COVERAGE.iter().map(|(error, _)| *error).collect();
+        assert_eq!(simplicity_errors, registered);
+
+        let unreachable: Vec<ScriptError> = simplicity_errors
+            .iter()
+            .filter(|error| coverage(**error) == Some(Coverage::Unreachable))
+            .copied()
+            .collect();
+        assert_eq!(
+            &[
+                ScriptError::SimplicityNotYetImplemented,
+                ScriptError::SimplicityAmr
+            ],
+            unreachable.as_slice()
+        );
+        assert_eq!(None, coverage(ScriptError::EvalFalse));
+    }
+
+    #[test]
+    fn simplicity_errors_are_counted() {
+        let test_case = TestBuilder::comment("wrong_length/no_script_inputs")
+            .human_encoding("main := unit", &HashMap::new())
+            .skip_script_inputs()
+            .expected_error(ScriptError::SimplicityWrongLength)
+            .finished();
+        let counts = error_counts(&[test_case]);
+        assert_eq!(ScriptError::ALL.len(), counts.len());
+        for (error, count) in &counts {
+            let expected = *error == ScriptError::SimplicityWrongLength;
+            assert_eq!(usize::from(expected), *count, "{error}");
+        }
+
+        let uncovered = uncovered_errors(&counts);
+        assert!(!uncovered.contains(&ScriptError::SimplicityWrongLength));
+        assert!(uncovered.contains(&ScriptError::SimplicityBitstreamEof));
+        assert!(uncovered.contains(&ScriptError::SimplicityHiddenRoot));
+        assert!(!uncovered.contains(&ScriptError::SimplicityAmr));
+        assert!(!uncovered.contains(&ScriptError::SimplicityNotYetImplemented));
+        assert!(!uncovered.contains(&ScriptError::EvalFalse));
+    }
+
+    #[test]
+    fn covering_cases_are_missing() {
+        let missing = missing_covering_cases(["wrong_length/no_script_inputs"]);
+        assert!(!missing.contains(&ScriptError::SimplicityWrongLength));
+        assert!(missing.contains(&ScriptError::SimplicityBitstreamEof));
+        assert!(!missing.contains(&ScriptError::SimplicityAmr));
+    }
+}
@@ -0,0 +1,185 @@
+//! # Bit flip corruption
+//!
+//! Test cases that flip single bits of a valid seed program, included with `--corruption`.
+//!
+//! The seed program `comp (comp (pair wit1 wit2) jet_lt_8) jet_verify` reads two witness bytes.
+//! Every bit position of its encoding is flipped, in the order of decoding,
+//! including the witness block but excluding the final padding.
+//! The first position that causes a given script error becomes a test case.
+
+use std::sync::Arc;
+
+use elements_miniscript as miniscript;
+use miniscript::elements;
+use miniscript::elements::hashes::Hash as _;
+use simplicity::bit_machine::ExecutionError;
+use simplicity::dag::{DagLike, InternalSharing};
+use simplicity::jet::elements::ElementsEnv;
+use simplicity::jet::Elements;
+use simplicity::node::Inner;
+use simplicity::{decode, types, BitIter, BitMachine, Cmr, RedeemNode, Value};
+
+use crate::json::{ScriptError, TestCase};
+use crate::test::TestBuilder;
+use crate::util;
+use crate::witness::WitnessBuilder;
+
+/// Valid program whose single bit flips are explored.
+///
+/// The program checks that the first witness byte is less than the second.
+/// The bytes are 0x7f and 0x80, so flipping the most significant bit of either makes the jet fail.
+const SEED: &str = "
+    wit1 := witness
+    wit2 := witness
+    main := comp (comp (pair wit1 wit2) jet_lt_8) jet_verify
+";
+
+/// Generate one test case per script error that a single bit flip of the seed program causes.
+///
+/// Every bit of the encoded seed program is flipped in turn, in the order of decoding,
+/// including the witness block but excluding the final padding.
+/// The first bit position that causes a given error is kept, the others are dropped.
+/// The CMR in the script stays the CMR of the seed program.
+///
+/// The expected error is computed by rust-simplicity, which checks less than the C implementation
+/// and sometimes in a different order. Flips whose outcome may differ between both are dropped:
+/// flips that keep the program valid, and flips whose error has no unique script error.
+pub fn corruption_cases() -> impl Iterator<Item = TestCase> {
+    let witness = WitnessBuilder::new()
+        .value("wit1", Value::u8(0x7f))
+        .value("wit2", Value::u8(0x80))
+        .to_map();
+    let seed = util::program_from_string(SEED, &witness);
+    let seed_bytes = seed.encode_to_vec();
+    let mut sink = simplicity::BitWriter::new(std::io::sink());
+    let seed_bit_len = seed.encode(&mut sink).expect("I/O to sink never fails");
+
+    let cmr = seed.cmr();
+    let (tx, prevouts, index) = util::minimal_spend_for_cmr(cmr);
+    let spend_info = util::get_spend_info(cmr, simplicity::leaf_version());
+    let control_block = util::get_control_block(cmr, simplicity::leaf_version(), &spend_info)
+        .expect("leaf exists")
+        .serialize();
+
+    let env = util::elements_env(
+        Arc::new(tx.clone()),
+        &prevouts,
+        index,
+        cmr,
+        &control_block,
+        elements::BlockHash::all_zeros(),
+    );
+
+    let mut errors = Vec::new();
+    (0..seed_bit_len).filter_map(move |bit_index| {
+        let bytes = util::corrupt_at(&seed_bytes, bit_index);
+        let error = rust_script_error(&bytes, cmr, &env)?;
+        if errors.contains(&error) {
+            return None;
+        }
+        errors.push(error);
+
+        let name = error.to_string();
+        let name = name.trim_start_matches("SIMPLICITY_").to_lowercase();
+        let test_case = TestBuilder::comment(format!("{name}/corrupt_bit_{bit_index}"))
+            .description("Single bit flip of a valid program")
+            .raw_program(bytes)
+            .raw_cmr(cmr)
+            .allow_cmr_mismatch()
+            .expected_error(error)
+            .finished();
+        assert_eq!(tx, test_case.tx.0);
+        Some(test_case)
+    })
+}
+
+/// Return the script error that rust-simplicity reports for the given program,
+/// which is committed under `cmr`.
+///
+/// Return `None` if the program is valid or if the error has no unique script error.
+fn rust_script_error(
+    bytes: &[u8],
+    cmr: Cmr,
+    env: &ElementsEnv<Arc<elements::Transaction>>,
+) -> Option<ScriptError> {
+    let mut bits = BitIter::from(bytes.iter().copied());
+    let program = match RedeemNode::<Elements>::decode(&mut bits) {
+        Ok(program) => program,
+        Err(simplicity::Error::Decode(error)) => {
+            return match error {
+                // Program ends early; the witness block has its own errors in C
+                decode::Error::EndOfStream => {
+                    let mut bits = BitIter::from(bytes.iter().copied());
+                    decode::decode_expression::<_, Elements>(&mut bits)
+                        .is_err()
+                        .then_some(ScriptError::SimplicityBitstreamEof)
+                }
+                decode::Error::BadIndex | decode::Error::InvalidJet => {
+                    Some(ScriptError::SimplicityDataOutOfRange)
+                }
+                decode::Error::NotInCanonicalOrder => Some(ScriptError::SimplicityDataOutOfOrder),
+                decode::Error::BothChildrenHidden => Some(ScriptError::SimplicityHidden),
+                decode::Error::Type(types::Error::OccursCheck) => {
+                    Some(ScriptError::SimplicityTypeInferenceOccursCheck)
+                }
+                decode::Error::Type(_) => Some(ScriptError::SimplicityTypeInferenceUnification),
+                // Hidden root and hidden child share an error,
+                // C checks sharing after the CMR,
+                // and the limits on naturals and program length differ
+                _ => None,
+            };
+        }
+        // The root is unified with 1 → 1 after the other types are inferred
+        Err(simplicity::Error::Type(_)) => {
+            return Some(ScriptError::SimplicityTypeInferenceNotProgram)
+        }
+        Err(_) => return None,
+    };
+
+    // C rejects fail nodes while decoding, before any other check
+    let has_fail = program
+        .as_ref()
+        .post_order_iter::<InternalSharing>()
+        .any(|data| matches!(data.node.inner(), Inner::Fail(_)));
+    if has_fail {
+        return Some(ScriptError::SimplicityFailCode);
+    }
+
+    let n_unread_bits = bytes.len() * 8 - bits.n_total_read();
+    if 8 <= n_unread_bits {
+        return Some(ScriptError::SimplicityBitstreamUnusedBytes);
+    }
+    if bits.any(|bit| bit) {
+        return Some(ScriptError::SimplicityBitstreamUnusedBits);
+    }
+    if program.cmr() != cmr {
+        return Some(ScriptError::SimplicityCmr);
+    }
+
+    let mut mac = BitMachine::for_program(&program);
+    match mac.exec(&program, env) {
+        Ok(_) => None,
+        Err(ExecutionError::JetFailed(_)) => Some(ScriptError::SimplicityExecJet),
+        Err(ExecutionError::ReachedPrunedBranch(_)) => Some(ScriptError::SimplicityExecAssert),
+        Err(ExecutionError::ReachedFailNode(_)) => unreachable!("fail nodes are rejected above"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn corruption_errors_are_unique() {
+        let test_cases: Vec<TestCase> = corruption_cases().collect();
+        assert!(!test_cases.is_empty());
+
+        let errors: Vec<_> = test_cases
+            .iter()
+            .map(|test_case| test_case.failure.as_ref().unwrap().error.unwrap())
+            .collect();
+        for (i, error) in errors.iter().enumerate() {
+            assert!(!errors[..i].contains(error), "{error} occurs twice");
+        }
+    }
+}
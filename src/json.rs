@@ -1,6 +1,8 @@
-use std::fmt;
+use std::borrow::Borrow;
 use std::str::FromStr;
+use std::{fmt, io};
 
+use elements::hashes::Hash as _;
 use elements::hex::{FromHex, ToHex};
 use elements_miniscript as miniscript;
 use miniscript::elements;
@@ -141,6 +143,12 @@ pub struct TestCase {
     #[serde(deserialize_with = "deserialize_flags")]
     pub flags: Vec<Flag>,
     pub comment: String,
+    /// Free-form rationale, such as a reference to a spec section.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    /// One-line description for the index of test cases. Not part of the JSON.
+    #[serde(skip)]
+    pub description: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub hash_genesis_block: Option<elements::BlockHash>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -149,6 +157,192 @@ pub struct TestCase {
     pub failure: Option<Parameters>,
     #[serde(rename = "final", skip_serializing_if = "std::ops::Not::not", default)]
     pub is_final: bool,
+    /// Final stack of the outer interpreter, for debugging the harness.
+    ///
+    /// The stack is empty if the Simplicity program succeeds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_final_stack: Option<Vec<Serde<Vec<u8>>>>,
+}
+
+impl ScriptError {
+    /// All script errors, in the order of their declaration.
+    pub const ALL: &'static [ScriptError] = &[
+        ScriptError::Ok,
+        ScriptError::UnknownError,
+        ScriptError::EvalFalse,
+        ScriptError::OpReturn,
+        // Max sizes
+        ScriptError::ScriptSize,
+        ScriptError::PushSize,
+        ScriptError::OpCount,
+        ScriptError::StackSize,
+        ScriptError::SigCount,
+        ScriptError::PubkeyCount,
+        // Failed verify operations
+        ScriptError::Verify,
+        ScriptError::EqualVerify,
+        ScriptError::CheckMultisigVerify,
+        ScriptError::CheckSigVerify,
+        ScriptError::NumEqualVerify,
+        // Logical/Format/Canonical errors
+        ScriptError::BadOpcode,
+        ScriptError::DisabledOpcode,
+        ScriptError::InvalidStackOperation,
+        ScriptError::InvalidAltstackOperation,
+        ScriptError::UnbalancedConditional,
+        // CHECKLOCKTIMEVERIFY and CHECKSEQUENCEVERIFY
+        ScriptError::NegativeLocktime,
+        ScriptError::UnsatisfiedLocktime,
+        // Malleability
+        ScriptError::SigHashtype,
+        ScriptError::SigDer,
+        ScriptError::MinimalData,
+        ScriptError::SigPushonly,
+        ScriptError::SigHighS,
+        ScriptError::SigNulldummy,
+        ScriptError::Pubkeytype,
+        ScriptError::Cleanstack,
+        ScriptError::Minimalif,
+        ScriptError::SigNullfail,
+        // Softfork safeness
+        ScriptError::DiscourageUpgradableNops,
+        ScriptError::DiscourageUpgradableWitnessProgram,
+        ScriptError::DiscourageUpgradableTaprootVersion,
+        ScriptError::DiscourageOpSuccess,
+        ScriptError::DiscourageUpgradablePubkeytype,
+        // Segregated witness
+        ScriptError::WitnessProgramWrongLength,
+        ScriptError::WitnessProgramWitnessEmpty,
+        ScriptError::WitnessProgramMismatch,
+        ScriptError::WitnessMalleated,
+        ScriptError::WitnessMalleatedP2sh,
+        ScriptError::WitnessUnexpected,
+        ScriptError::WitnessPubkeytype,
+        // Taproot
+        ScriptError::SchnorrSigSize,
+        ScriptError::SchnorrSigHashtype,
+        ScriptError::SchnorrSig,
+        ScriptError::TaprootWrongControlSize,
+        ScriptError::TapscriptValidationWeight,
+        ScriptError::TapscriptCheckMultisig,
+        ScriptError::TapscriptMinimalif,
+        // Constant scriptCode
+        ScriptError::OpCodeseparator,
+        ScriptError::SigFindanddelete,
+        // Elements
+        ScriptError::Rangeproof,
+        ScriptError::PedersenTally,
+        // Elements: New tapscript related errors
+        ScriptError::Sha2ContextLoad,
+        ScriptError::Sha2ContextWrite,
+        ScriptError::IntrospectContextUnavailable,
+        ScriptError::IntrospectIndexOutOfBounds,
+        ScriptError::Expected8bytes,
+        ScriptError::Arithmetic64,
+        ScriptError::Ecmultverifyfail,
+        // Elements: Simplicity related errors
+        ScriptError::SimplicityWrongLength,
+        ScriptError::SimplicityBitstreamEof,
+        ScriptError::SimplicityNotYetImplemented,
+        ScriptError::SimplicityDataOutOfRange,
+        ScriptError::SimplicityDataOutOfOrder,
+        ScriptError::SimplicityFailCode,
+        ScriptError::SimplicityStopCode,
+        ScriptError::SimplicityHidden,
+        ScriptError::SimplicityBitstreamUnusedBytes,
+        ScriptError::SimplicityBitstreamUnusedBits,
+        ScriptError::SimplicityTypeInferenceUnification,
+        ScriptError::SimplicityTypeInferenceOccursCheck,
+        ScriptError::SimplicityTypeInferenceNotProgram,
+        ScriptError::SimplicityWitnessEof,
+        ScriptError::SimplicityWitnessUnusedBits,
+        ScriptError::SimplicityUnsharedSubexpression,
+        ScriptError::SimplicityCmr,
+        ScriptError::SimplicityAmr,
+        ScriptError::SimplicityExecBudget,
+        ScriptError::SimplicityExecMemory,
+        ScriptError::SimplicityExecJet,
+        ScriptError::SimplicityExecAssert,
+        ScriptError::SimplicityAntidos,
+        ScriptError::SimplicityHiddenRoot,
+    ];
+
+    /// Legacy spellings of script errors that were renamed upstream.
+    ///
+    /// [`FromStr`] accepts these in addition to the canonical spellings.
+    /// [`fmt::Display`] always produces the canonical spelling.
+    pub const ALIASES: &'static [(&'static str, ScriptError)] = &[
+        (
+            "SIMPLICITY_BITSTREAM_TRAILING_BYTES",
+            ScriptError::SimplicityBitstreamUnusedBytes,
+        ),
+        (
+            "SIMPLICITY_BITSTREAM_ILLEGAL_PADDING",
+            ScriptError::SimplicityBitstreamUnusedBits,
+        ),
+        (
+            "SIMPLICITY_WITNESS_TRAILING_BITS",
+            ScriptError::SimplicityWitnessUnusedBits,
+        ),
+    ];
+}
+
+/// Phase of spending a Simplicity program in which a script error is raised.
+///
+/// Phases are ordered as the C implementation runs them.
+/// A program that raises an error in one phase passed all earlier phases.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+pub enum Phase {
+    /// Check the taproot witness stack before the program is decoded.
+    Spend,
+    /// Decode the program, the witness block and the padding.
+    Parse,
+    /// Compare the CMR of the program to the script.
+    Commitment,
+    /// Infer types and fill in the witness values, which need the types.
+    TypeCheck,
+    /// Check that no subexpression is duplicated, then check the AMR.
+    SharingCheck,
+    /// Check static bounds and run the Bit Machine.
+    Execution,
+}
+
+impl ScriptError {
+    /// Return the phase in which the script error is raised.
+    ///
+    /// `Ok` is raised after execution.
+    /// Return `None` if the error is not raised while spending a Simplicity program.
+    pub fn phase(self) -> Option<Phase> {
+        match self {
+            ScriptError::SimplicityWrongLength => Some(Phase::Spend),
+            ScriptError::SimplicityBitstreamEof
+            | ScriptError::SimplicityNotYetImplemented
+            | ScriptError::SimplicityDataOutOfRange
+            | ScriptError::SimplicityDataOutOfOrder
+            | ScriptError::SimplicityFailCode
+            | ScriptError::SimplicityStopCode
+            | ScriptError::SimplicityHidden
+            | ScriptError::SimplicityHiddenRoot
+            | ScriptError::SimplicityBitstreamUnusedBytes
+            | ScriptError::SimplicityBitstreamUnusedBits => Some(Phase::Parse),
+            ScriptError::SimplicityCmr => Some(Phase::Commitment),
+            ScriptError::SimplicityTypeInferenceUnification
+            | ScriptError::SimplicityTypeInferenceOccursCheck
+            | ScriptError::SimplicityTypeInferenceNotProgram
+            | ScriptError::SimplicityWitnessEof
+            | ScriptError::SimplicityWitnessUnusedBits => Some(Phase::TypeCheck),
+            ScriptError::SimplicityUnsharedSubexpression | ScriptError::SimplicityAmr => {
+                Some(Phase::SharingCheck)
+            }
+            ScriptError::SimplicityExecBudget
+            | ScriptError::SimplicityExecMemory
+            | ScriptError::SimplicityExecJet
+            | ScriptError::SimplicityExecAssert
+            | ScriptError::SimplicityAntidos
+            | ScriptError::Ok => Some(Phase::Execution),
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Display for ScriptError {
@@ -337,8 +531,219 @@ impl FromStr for ScriptError {
             "SIMPLICITY_EXEC_ASSERT" => Ok(ScriptError::SimplicityExecAssert),
             "SIMPLICITY_ANTIDOS" => Ok(ScriptError::SimplicityAntidos),
             "SIMPLICITY_HIDDEN_ROOT" => Ok(ScriptError::SimplicityHiddenRoot),
-            _ => Err("unknown error"),
+            _ => ScriptError::ALIASES
+                .iter()
+                .find(|(alias, _)| *alias == s)
+                .map(|(_, error)| *error)
+                .ok_or("unknown error"),
+        }
+    }
+}
+
+/// Write the test cases as a JSON array, one test case at a time.
+///
+/// The output is the same as that of [`serde_json::to_string_pretty`] or [`serde_json::to_string`],
+/// but the array is never held in memory as a whole.
+pub fn write_json_array<I, W>(test_cases: I, w: W, pretty: bool) -> io::Result<()>
+where
+    I: IntoIterator,
+    I::Item: Borrow<TestCase>,
+    W: io::Write,
+{
+    fn write_seq<I, W, F>(
+        test_cases: I,
+        mut serializer: serde_json::Serializer<W, F>,
+    ) -> serde_json::Result<()>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<TestCase>,
+        W: io::Write,
+        F: serde_json::ser::Formatter,
+    {
+        use serde::ser::SerializeSeq;
+
+        let mut seq = serializer.serialize_seq(None)?;
+        for test_case in test_cases {
+            seq.serialize_element(test_case.borrow())?;
+        }
+        seq.end()
+    }
+
+    let result = if pretty {
+        write_seq(test_cases, serde_json::Serializer::pretty(w))
+    } else {
+        write_seq(test_cases, serde_json::Serializer::new(w))
+    };
+    result.map_err(io::Error::from)
+}
+
+/// Write the test cases as JSON lines.
+///
+/// Each test case is written as compact JSON on a line of its own.
+pub fn write_json_lines<I, W>(test_cases: I, mut w: W) -> io::Result<()>
+where
+    I: IntoIterator,
+    I::Item: Borrow<TestCase>,
+    W: io::Write,
+{
+    for test_case in test_cases {
+        serde_json::to_writer(&mut w, test_case.borrow())?;
+        w.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+/// Write the test cases as an indefinite-length CBOR array, one test case at a time.
+///
+/// Byte strings such as transactions and witness elements are written as CBOR byte strings
+/// instead of hex strings.
+pub fn write_cbor<I, W>(test_cases: I, mut w: W) -> io::Result<()>
+where
+    I: IntoIterator,
+    I::Item: Borrow<TestCase>,
+    W: io::Write,
+{
+    /// Initial byte of an indefinite-length array.
+    const ARRAY_START: u8 = 0x9f;
+    /// Byte that ends an indefinite-length item.
+    const BREAK: u8 = 0xff;
+
+    w.write_all(&[ARRAY_START])?;
+    for test_case in test_cases {
+        ciborium::into_writer(test_case.borrow(), &mut w).map_err(|error| match error {
+            ciborium::ser::Error::Io(error) => error,
+            ciborium::ser::Error::Value(message) => io::Error::other(message),
+        })?;
+    }
+    w.write_all(&[BREAK])
+}
+
+/// Write a plain-text index of the test cases.
+///
+/// Each test case is written on a line of its own:
+/// comment, expected error and description, separated by tabs.
+pub fn write_index<I, W>(test_cases: I, mut w: W) -> io::Result<()>
+where
+    I: IntoIterator,
+    I::Item: Borrow<TestCase>,
+    W: io::Write,
+{
+    for test_case in test_cases {
+        write_index_entry(test_case.borrow(), &mut w)?;
+    }
+    Ok(())
+}
+
+/// Write the line of the given test case in the index.
+pub fn write_index_entry<W: io::Write>(test_case: &TestCase, mut w: W) -> io::Result<()> {
+    let error = test_case
+        .failure
+        .as_ref()
+        .and_then(|failure| failure.error)
+        .unwrap_or(ScriptError::Ok);
+    let description = test_case.description.as_deref().unwrap_or("");
+    writeln!(w, "{}\t{}\t{}", test_case.comment, error, description)
+}
+
+/// Programs longer than this many bytes are too long to review, so they are not rendered.
+const MAX_RENDERED_PROGRAM_LEN: usize = 2_000;
+
+/// Write the human encoding of the program of each test case.
+///
+/// Each program is preceded by a line with the comment of its test case.
+/// Programs that are too long or that do not decode are noted as such.
+pub fn write_programs<I, W>(test_cases: I, mut w: W) -> io::Result<()>
+where
+    I: IntoIterator,
+    I::Item: Borrow<TestCase>,
+    W: io::Write,
+{
+    for test_case in test_cases {
+        write_program(test_case.borrow(), &mut w)?;
+    }
+    Ok(())
+}
+
+/// Write the human encoding of the program of the given test case.
+pub fn write_program<W: io::Write>(test_case: &TestCase, mut w: W) -> io::Result<()> {
+    writeln!(w, "-- {}", test_case.comment)?;
+    match test_case.program_cmr() {
+        Some((program, _)) if MAX_RENDERED_PROGRAM_LEN < program.len() => {
+            writeln!(w, "-- Program is too long ({} bytes)", program.len())?
         }
+        Some((program, cmr)) => match crate::util::decompile(program, cmr) {
+            Some(s) => writeln!(w, "{}", s.trim())?,
+            None => writeln!(w, "-- Program does not decode")?,
+        },
+        None => writeln!(w, "-- Program does not decode")?,
+    }
+    writeln!(w)
+}
+
+impl TestCase {
+    /// Return the program bytes and the CMR from the witness stack of the test case.
+    ///
+    /// The witness stack is `[program, script, control block]`, optionally followed by an annex.
+    /// The script is the CMR.
+    ///
+    /// Return `None` if the witness stack has a different shape.
+    pub fn program_cmr(&self) -> Option<(&[u8], &[u8])> {
+        let parameters = self.success.as_ref().or(self.failure.as_ref())?;
+        let mut witness: Vec<&[u8]> = parameters.witness.iter().map(|x| x.0.as_slice()).collect();
+        if 2 <= witness.len() && witness.last()?.first() == Some(&0x50) {
+            witness.pop();
+        }
+        match witness.as_slice() {
+            [program, cmr, _control_block] => Some((program, cmr)),
+            _ => None,
+        }
+    }
+
+    /// Return the leaf version from the control block of the test case.
+    ///
+    /// Return `None` if the witness stack has no control block.
+    pub fn leaf_version(&self) -> Option<u8> {
+        let parameters = self.success.as_ref().or(self.failure.as_ref())?;
+        let mut witness: Vec<&[u8]> = parameters.witness.iter().map(|x| x.0.as_slice()).collect();
+        if 2 <= witness.len() && witness.last()?.first() == Some(&0x50) {
+            witness.pop();
+        }
+        match witness.as_slice() {
+            [.., _script, control_block] => Some(control_block.first()? & 0xfe),
+            _ => None,
+        }
+    }
+
+    /// Return the message that `jet_sig_all_hash` computes when the test case is run.
+    ///
+    /// The message commits to the transaction, so it can be signed only after
+    /// the test case has been finished with a placeholder signature of the same length.
+    ///
+    /// ## Panics
+    ///
+    /// The witness stack has an annex or it is not `[program, script, control block]`.
+    pub fn sighash_all(&self) -> [u8; 32] {
+        let parameters = self.success.as_ref().or(self.failure.as_ref());
+        let witness: Vec<&[u8]> = parameters
+            .map(|parameters| parameters.witness.iter().map(|x| x.0.as_slice()).collect())
+            .unwrap_or_default();
+        let [_program, cmr, control_block] = witness.as_slice() else {
+            panic!("Witness stack is not [program, script, control block]");
+        };
+        let cmr = simplicity::Cmr::from_byte_array((*cmr).try_into().expect("32-byte CMR"));
+        let prevouts: Vec<elements::TxOut> = self.prevouts.iter().map(|x| x.0.clone()).collect();
+        // The script assets tests default to a genesis block hash of all zeroes
+        let genesis_hash = self
+            .hash_genesis_block
+            .unwrap_or(elements::BlockHash::all_zeros());
+        crate::util::sighash_all(
+            &self.tx.0,
+            &prevouts,
+            self.index,
+            cmr,
+            control_block,
+            genesis_hash,
+        )
     }
 }
 
@@ -400,13 +805,19 @@ impl FromStr for Flag {
     }
 }
 
+/// Human-readable formats such as JSON get a hex string.
+/// Binary formats such as CBOR get a byte string.
 impl<A: elements::pset::serialize::Serialize> Serialize for Serde<A> {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
         let bytes = self.0.serialize();
-        serializer.serialize_str(&bytes.to_hex())
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&bytes.to_hex())
+        } else {
+            serializer.serialize_bytes(&bytes)
+        }
     }
 }
 
@@ -415,13 +826,36 @@ impl<'de, A: elements::pset::serialize::Deserialize> Deserialize<'de> for Serde<
     where
         D: Deserializer<'de>,
     {
-        let hex: String = Deserialize::deserialize(deserializer)?;
-        let bytes = Vec::<u8>::from_hex(&hex).map_err(D::Error::custom)?;
+        let bytes = if deserializer.is_human_readable() {
+            let hex: String = Deserialize::deserialize(deserializer)?;
+            Vec::<u8>::from_hex(&hex).map_err(D::Error::custom)?
+        } else {
+            deserializer.deserialize_byte_buf(BytesVisitor)?
+        };
         let inner = A::deserialize(&bytes).map_err(D::Error::custom)?;
         Ok(Serde(inner))
     }
 }
 
+/// Visitor of a byte string in a binary format.
+struct BytesVisitor;
+
+impl<'de> serde::de::Visitor<'de> for BytesVisitor {
+    type Value = Vec<u8>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a byte string")
+    }
+
+    fn visit_bytes<E: serde::de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+        Ok(v.to_vec())
+    }
+
+    fn visit_byte_buf<E: serde::de::Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+        Ok(v)
+    }
+}
+
 // https://github.com/serde-rs/serde/issues/1316
 impl Serialize for ScriptError {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
@@ -443,12 +877,15 @@ impl<'de> Deserialize<'de> for ScriptError {
     }
 }
 
+/// Serialize the flags in the canonical order of [`Flag::all_flags()`],
+/// so the same set of flags always serializes to the same string.
 fn serialize_flags<S>(flags: &[Flag], serializer: S) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
 {
-    let s = flags
+    let s = Flag::all_flags()
         .iter()
+        .filter(|f| flags.contains(f))
         .map(|f| f.to_string())
         .collect::<Vec<String>>()
         .join(",");
@@ -469,9 +906,25 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test::TestBuilder;
+    use std::collections::HashMap;
     use std::fs::File;
     use std::io::Read;
 
+    /// Return two small test cases that succeed.
+    fn sample_test_cases() -> Vec<TestCase> {
+        let empty_witness = HashMap::new();
+        ["main := unit", "main := iden"]
+            .into_iter()
+            .map(|s| {
+                TestBuilder::comment(s)
+                    .human_encoding(s, &empty_witness)
+                    .expected_error(ScriptError::Ok)
+                    .finished()
+            })
+            .collect()
+    }
+
     #[test]
     fn serialize_roundtrip() {
         let txout = elements::TxOut::default();
@@ -492,10 +945,13 @@ mod tests {
             index: 0,
             flags: Flag::all_flags().to_vec(),
             comment: "my awesome comment".to_string(),
+            note: None,
+            description: None,
             hash_genesis_block: None,
             success: None,
             failure: Some(parameters.clone()),
             is_final: true,
+            expected_final_stack: None,
         };
 
         let s = serde_json::to_string(&test_case).expect("serialize");
@@ -504,10 +960,109 @@ mod tests {
 
         test_case.is_final = false;
         let s = serde_json::to_string(&test_case).expect("serialize");
+        assert!(!s.contains("\"note\""));
+        let original: TestCase = serde_json::from_str(&s).expect("deserialize");
+        assert_eq!(test_case, original);
+
+        test_case.note = Some("see section 7.2".to_string());
+        let s = serde_json::to_string(&test_case).expect("serialize");
+        assert!(s.contains("\"note\":\"see section 7.2\""));
+        let original: TestCase = serde_json::from_str(&s).expect("deserialize");
+        assert_eq!(test_case, original);
+
+        assert!(!s.contains("\"expected_final_stack\""));
+        test_case.expected_final_stack = Some(vec![Serde(vec![]), Serde(vec![0x01])]);
+        let s = serde_json::to_string(&test_case).expect("serialize");
+        assert!(s.contains("\"expected_final_stack\":[\"\",\"01\"]"));
         let original: TestCase = serde_json::from_str(&s).expect("deserialize");
         assert_eq!(test_case, original);
     }
 
+    #[test]
+    fn json_lines_roundtrip() {
+        let test_cases = sample_test_cases();
+
+        let mut bytes = Vec::new();
+        write_json_lines(&test_cases, &mut bytes).expect("I/O to vector never fails");
+        let s = String::from_utf8(bytes).expect("JSON is UTF-8");
+        assert_eq!(test_cases.len(), s.lines().count());
+
+        let original: Vec<TestCase> = s
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("deserialize"))
+            .collect();
+        assert_eq!(test_cases, original);
+    }
+
+    #[test]
+    fn json_array_matches_serde_json() {
+        let test_cases = sample_test_cases();
+
+        for test_cases in [&test_cases[..0], &test_cases[..]] {
+            let mut pretty = Vec::new();
+            write_json_array(test_cases, &mut pretty, true).expect("I/O to vector never fails");
+            let expected = serde_json::to_string_pretty(test_cases).expect("serialize");
+            assert_eq!(expected.as_bytes(), pretty.as_slice());
+
+            let mut compact = Vec::new();
+            write_json_array(test_cases, &mut compact, false).expect("I/O to vector never fails");
+            let expected = serde_json::to_string(test_cases).expect("serialize");
+            assert_eq!(expected.as_bytes(), compact.as_slice());
+        }
+    }
+
+    #[test]
+    fn cbor_roundtrip() {
+        let test_cases = sample_test_cases();
+
+        let mut bytes = Vec::new();
+        write_cbor(&test_cases, &mut bytes).expect("I/O to vector never fails");
+        let original: Vec<TestCase> = ciborium::from_reader(bytes.as_slice()).expect("deserialize");
+        assert_eq!(test_cases, original);
+    }
+
+    #[test]
+    fn cbor_has_byte_strings() {
+        let mut bytes = Vec::new();
+        ciborium::into_writer(&Serde(vec![0xde, 0xad, 0xbe, 0xef]), &mut bytes)
+            .expect("I/O to vector never fails");
+        // Major type 2 (byte string) of length 4
+        assert_eq!(vec![0x44, 0xde, 0xad, 0xbe, 0xef], bytes);
+    }
+
+    #[test]
+    fn index_has_line_per_case() {
+        let empty_witness = HashMap::new();
+        let test_cases: Vec<TestCase> = [
+            ("main := unit", ScriptError::Ok),
+            (
+                "main := comp (const 0b0) jet_verify",
+                ScriptError::SimplicityExecJet,
+            ),
+        ]
+        .into_iter()
+        .map(|(s, error)| {
+            TestBuilder::comment(s)
+                .description("description")
+                .human_encoding(s, &empty_witness)
+                .expected_error(error)
+                .finished()
+        })
+        .collect();
+
+        let mut bytes = Vec::new();
+        write_index(&test_cases, &mut bytes).expect("I/O to vector never fails");
+        let s = String::from_utf8(bytes).expect("index is UTF-8");
+        let lines: Vec<&str> = s.lines().collect();
+        assert_eq!(
+            vec![
+                "main := unit\tOK\tdescription",
+                "main := comp (const 0b0) jet_verify\tSIMPLICITY_EXEC_JET\tdescription",
+            ],
+            lines
+        );
+    }
+
     #[test]
     fn deserialize_single() {
         let s = r#"{
@@ -535,6 +1090,186 @@ mod tests {
             .expect("Unable to read file");
 
         let data: Vec<TestCase> = serde_json::from_str(&contents).expect("Unable to parse JSON");
-        assert_eq!(250, data.len());
+        let error = |comment: &str| {
+            let test_case = data
+                .iter()
+                .find(|test_case| test_case.comment == comment)
+                .unwrap_or_else(|| panic!("Fixture has no test case {comment}"));
+            match (&test_case.success, &test_case.failure) {
+                (Some(_), None) => ScriptError::Ok,
+                (None, Some(parameters)) => parameters.error.expect("failure has an error"),
+                _ => panic!("{comment} is either success or failure"),
+            }
+        };
+        assert_eq!("ok/unit", data[0].comment);
+        assert_eq!(ScriptError::Ok, error("ok/unit"));
+        assert_eq!(ScriptError::Ok, error("ok/input_amounts_sum"));
+        assert_eq!(
+            ScriptError::SimplicityExecJet,
+            error("exec_jet/input_amounts_sum_mismatch")
+        );
+        assert_eq!(
+            ScriptError::SimplicityHiddenRoot,
+            error("hidden_root/hidden_root")
+        );
+
+        let mut serialized = Vec::new();
+        write_json_array(&data, &mut serialized, true).expect("Unable to write data");
+        assert_eq!(contents.as_bytes(), serialized.as_slice());
+    }
+
+    #[test]
+    fn flag_order_independence() {
+        let flags = Flag::all_flags();
+        let canonical = serialize_flags(&flags, serde_json::value::Serializer).unwrap();
+
+        let mut reversed = flags;
+        reversed.reverse();
+        let mut rotated = flags;
+        rotated.rotate_left(3);
+        let mut swapped = flags;
+        swapped.swap(0, 7);
+
+        for permutation in [reversed, rotated, swapped] {
+            let serialized = serialize_flags(&permutation, serde_json::value::Serializer).unwrap();
+            assert_eq!(canonical, serialized);
+        }
+
+        let subset = serialize_flags(
+            &[Flag::Simplicity, Flag::Taproot, Flag::P2SH],
+            serde_json::value::Serializer,
+        )
+        .unwrap();
+        assert_eq!("P2SH,TAPROOT,SIMPLICITY", subset);
+    }
+
+    /// Position of the given error in [`ScriptError::ALL`].
+    ///
+    /// The match has no wildcard, so a new variant fails to compile until it is listed here
+    /// and [`N_ERRORS`] is bumped, which in turn fails to compile until `ALL` lists it.
+    const fn position(error: ScriptError) -> usize {
+        match error {
+            ScriptError::Ok => 0,
+            ScriptError::UnknownError => 1,
+            ScriptError::EvalFalse => 2,
+            ScriptError::OpReturn => 3,
+            ScriptError::ScriptSize => 4,
+            ScriptError::PushSize => 5,
+            ScriptError::OpCount => 6,
+            ScriptError::StackSize => 7,
+            ScriptError::SigCount => 8,
+            ScriptError::PubkeyCount => 9,
+            ScriptError::Verify => 10,
+            ScriptError::EqualVerify => 11,
+            ScriptError::CheckMultisigVerify => 12,
+            ScriptError::CheckSigVerify => 13,
+            ScriptError::NumEqualVerify => 14,
+            ScriptError::BadOpcode => 15,
+            ScriptError::DisabledOpcode => 16,
+            ScriptError::InvalidStackOperation => 17,
+            ScriptError::InvalidAltstackOperation => 18,
+            ScriptError::UnbalancedConditional => 19,
+            ScriptError::NegativeLocktime => 20,
+            ScriptError::UnsatisfiedLocktime => 21,
+            ScriptError::SigHashtype => 22,
+            ScriptError::SigDer => 23,
+            ScriptError::MinimalData => 24,
+            ScriptError::SigPushonly => 25,
+            ScriptError::SigHighS => 26,
+            ScriptError::SigNulldummy => 27,
+            ScriptError::Pubkeytype => 28,
+            ScriptError::Cleanstack => 29,
+            ScriptError::Minimalif => 30,
+            ScriptError::SigNullfail => 31,
+            ScriptError::DiscourageUpgradableNops => 32,
+            ScriptError::DiscourageUpgradableWitnessProgram => 33,
+            ScriptError::DiscourageUpgradableTaprootVersion => 34,
+            ScriptError::DiscourageOpSuccess => 35,
+            ScriptError::DiscourageUpgradablePubkeytype => 36,
+            ScriptError::WitnessProgramWrongLength => 37,
+            ScriptError::WitnessProgramWitnessEmpty => 38,
+            ScriptError::WitnessProgramMismatch => 39,
+            ScriptError::WitnessMalleated => 40,
+            ScriptError::WitnessMalleatedP2sh => 41,
+            ScriptError::WitnessUnexpected => 42,
+            ScriptError::WitnessPubkeytype => 43,
+            ScriptError::SchnorrSigSize => 44,
+            ScriptError::SchnorrSigHashtype => 45,
+            ScriptError::SchnorrSig => 46,
+            ScriptError::TaprootWrongControlSize => 47,
+            ScriptError::TapscriptValidationWeight => 48,
+            ScriptError::TapscriptCheckMultisig => 49,
+            ScriptError::TapscriptMinimalif => 50,
+            ScriptError::OpCodeseparator => 51,
+            ScriptError::SigFindanddelete => 52,
+            ScriptError::Rangeproof => 53,
+            ScriptError::PedersenTally => 54,
+            ScriptError::Sha2ContextLoad => 55,
+            ScriptError::Sha2ContextWrite => 56,
+            ScriptError::IntrospectContextUnavailable => 57,
+            ScriptError::IntrospectIndexOutOfBounds => 58,
+            ScriptError::Expected8bytes => 59,
+            ScriptError::Arithmetic64 => 60,
+            ScriptError::Ecmultverifyfail => 61,
+            ScriptError::SimplicityWrongLength => 62,
+            ScriptError::SimplicityBitstreamEof => 63,
+            ScriptError::SimplicityNotYetImplemented => 64,
+            ScriptError::SimplicityDataOutOfRange => 65,
+            ScriptError::SimplicityDataOutOfOrder => 66,
+            ScriptError::SimplicityFailCode => 67,
+            ScriptError::SimplicityStopCode => 68,
+            ScriptError::SimplicityHidden => 69,
+            ScriptError::SimplicityBitstreamUnusedBytes => 70,
+            ScriptError::SimplicityBitstreamUnusedBits => 71,
+            ScriptError::SimplicityTypeInferenceUnification => 72,
+            ScriptError::SimplicityTypeInferenceOccursCheck => 73,
+            ScriptError::SimplicityTypeInferenceNotProgram => 74,
+            ScriptError::SimplicityWitnessEof => 75,
+            ScriptError::SimplicityWitnessUnusedBits => 76,
+            ScriptError::SimplicityUnsharedSubexpression => 77,
+            ScriptError::SimplicityCmr => 78,
+            ScriptError::SimplicityAmr => 79,
+            ScriptError::SimplicityExecBudget => 80,
+            ScriptError::SimplicityExecMemory => 81,
+            ScriptError::SimplicityExecJet => 82,
+            ScriptError::SimplicityExecAssert => 83,
+            ScriptError::SimplicityAntidos => 84,
+            ScriptError::SimplicityHiddenRoot => 85,
+        }
+    }
+
+    /// Number of variants of [`ScriptError`].
+    const N_ERRORS: usize = 86;
+    const _: () = {
+        assert!(ScriptError::ALL.len() == N_ERRORS);
+        let mut index = 0;
+        while index < N_ERRORS {
+            assert!(position(ScriptError::ALL[index]) == index);
+            index += 1;
+        }
+    };
+
+    #[test]
+    fn script_error_all() {
+        for error in ScriptError::ALL {
+            assert_eq!(Ok(*error), ScriptError::from_str(&error.to_string()));
+        }
+    }
+
+    #[test]
+    fn script_error_aliases() {
+        for (alias, error) in ScriptError::ALIASES {
+            assert_eq!(Ok(*error), ScriptError::from_str(alias));
+            assert_ne!(*alias, error.to_string());
+        }
+
+        let error: ScriptError =
+            serde_json::from_str("\"SIMPLICITY_WITNESS_TRAILING_BITS\"").expect("alias");
+        assert_eq!(ScriptError::SimplicityWitnessUnusedBits, error);
+        assert_eq!(
+            "\"SIMPLICITY_WITNESS_UNUSED_BITS\"",
+            serde_json::to_string(&error).unwrap()
+        );
+        assert!(ScriptError::from_str("SIMPLICITY_NO_SUCH_ERROR").is_err());
     }
 }
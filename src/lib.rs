@@ -0,0 +1,16 @@
+//! # Simplicity QA asset generator
+//!
+//! Generate script assets test vectors for Simplicity on Elements.
+//!
+//! The binary writes the test cases of this crate to JSON.
+//! Other tools can build their own test cases with [`test::TestBuilder`]
+//! and serialize them with the same schema via [`json::TestCase`].
+
+pub mod bit_encoding;
+pub mod cases;
+pub mod coverage;
+pub mod fuzz;
+pub mod json;
+pub mod test;
+pub mod util;
+pub mod witness;
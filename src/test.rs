@@ -1,14 +1,34 @@
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
 use elements_miniscript as miniscript;
 use miniscript::elements;
+use miniscript::elements::hashes::Hash as _;
+use miniscript::elements::secp256k1_zkp;
+use simplicity::bit_machine::ExecutionError;
 use simplicity::jet::Elements;
-use simplicity::{Cost, RedeemNode};
+use simplicity::{BitIter, BitMachine, Cost, RedeemNode};
 
-use crate::json::{Flag, Parameters, ScriptError, Serde, TestCase};
+use crate::json::{Flag, Parameters, Phase, ScriptError, Serde, TestCase};
 use crate::util;
 
+/// Whether finishing a test case prints diagnostics.
+static VERBOSE: AtomicBool = AtomicBool::new(false);
+
+/// Print diagnostics to stdout while finishing test cases:
+/// the budget of each witness stack, the arms whose self-check is skipped
+/// and the test cases whose CMR check is skipped.
+///
+/// Diagnostics are off by default.
+pub fn set_verbose(verbose: bool) {
+    VERBOSE.store(verbose, Ordering::Relaxed);
+}
+
+fn is_verbose() -> bool {
+    VERBOSE.load(Ordering::Relaxed)
+}
+
 pub trait MaybeBytes {}
 pub struct NoBytes;
 pub struct Bytes(Vec<u8>);
@@ -30,50 +50,172 @@ impl MaybeError for Error {}
 #[derive(Debug)]
 pub struct TestBuilder<B: MaybeBytes, C: MaybeCmr, E: MaybeError> {
     comment: String,
+    note: Option<String>,
+    expected_final_stack: Option<Vec<Vec<u8>>>,
+    description: Option<String>,
     program_bytes: B,
     cmr: C,
     extra_script_inputs: Vec<Vec<u8>>,
     cost: Option<Cost>,
+    padding_factor: f64,
     error: E,
     skip_script_inputs: bool,
+    skip_control_block: bool,
+    control_block_internal_key: Option<secp256k1_zkp::XOnlyPublicKey>,
+    allow_cmr_mismatch: bool,
+    cmr_check: bool,
+    large_stack: bool,
+    leaf_version: elements::taproot::LeafVersion,
+    annex: Option<Vec<u8>>,
+    sequence: elements::Sequence,
+    lock_time: elements::LockTime,
+    outputs: Vec<elements::TxOut>,
+    prevout_asset: elements::confidential::Asset,
+    prevout_value: elements::confidential::Value,
+    prevout_script_pubkey: Option<elements::Script>,
+    genesis_hash: Option<elements::BlockHash>,
+    self_check: bool,
+    key_path_keypair: Option<secp256k1_zkp::Keypair>,
+    sibling_leaves: Vec<(u8, elements::Script)>,
+    extra_inputs: Vec<(elements::TxIn, elements::TxOut)>,
+    input_index: usize,
+    extra_annex: Option<Vec<u8>>,
+    mangle_control_block: Option<fn(Vec<u8>) -> Vec<u8>>,
 }
 
 impl TestBuilder<NoBytes, NoCmr, NoError> {
     pub fn comment<A: Into<String>>(comment: A) -> Self {
         Self {
             comment: comment.into(),
+            note: None,
+            expected_final_stack: None,
+            description: None,
             program_bytes: NoBytes,
             cmr: NoCmr,
             extra_script_inputs: vec![],
             cost: None,
+            padding_factor: 1.0,
             error: NoError,
             skip_script_inputs: false,
+            skip_control_block: false,
+            control_block_internal_key: None,
+            allow_cmr_mismatch: false,
+            cmr_check: true,
+            large_stack: false,
+            leaf_version: simplicity::leaf_version(),
+            annex: None,
+            sequence: elements::Sequence::MAX,
+            lock_time: elements::LockTime::ZERO,
+            outputs: vec![elements::TxOut::default()],
+            prevout_asset: elements::confidential::Asset::Null,
+            prevout_value: elements::confidential::Value::Null,
+            prevout_script_pubkey: None,
+            genesis_hash: None,
+            self_check: false,
+            key_path_keypair: None,
+            sibling_leaves: vec![],
+            extra_inputs: vec![],
+            input_index: 0,
+            extra_annex: None,
+            mangle_control_block: None,
         }
     }
 }
 
 impl<B: MaybeBytes, C: MaybeCmr, E: MaybeError> TestBuilder<B, C, E> {
+    /// Record why the test case exists, for example by referencing a spec section.
+    ///
+    /// Unlike the description, the note is part of the JSON.
+    pub fn note<A: Into<String>>(mut self, note: A) -> Self {
+        self.note = Some(note.into());
+        self
+    }
+
+    /// Record the final stack of the outer interpreter, for debugging the harness.
+    ///
+    /// The stack is empty if the Simplicity program succeeds. Like the note, it is part of the JSON.
+    pub fn expected_final_stack(mut self, stack: Vec<Vec<u8>>) -> Self {
+        self.expected_final_stack = Some(stack);
+        self
+    }
+
+    /// Describe the test case in one line for the index of test cases.
+    pub fn description<A: Into<String>>(mut self, description: A) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
     pub fn raw_program(self, bytes: Vec<u8>) -> TestBuilder<Bytes, C, E> {
         TestBuilder {
             comment: self.comment,
+            note: self.note,
+            expected_final_stack: self.expected_final_stack,
+            description: self.description,
             program_bytes: Bytes(bytes),
             cmr: self.cmr,
             extra_script_inputs: self.extra_script_inputs,
             cost: self.cost,
+            padding_factor: self.padding_factor,
             error: self.error,
             skip_script_inputs: self.skip_script_inputs,
+            skip_control_block: self.skip_control_block,
+            control_block_internal_key: self.control_block_internal_key,
+            allow_cmr_mismatch: self.allow_cmr_mismatch,
+            cmr_check: self.cmr_check,
+            large_stack: self.large_stack,
+            leaf_version: self.leaf_version,
+            annex: self.annex,
+            sequence: self.sequence,
+            lock_time: self.lock_time,
+            outputs: self.outputs,
+            prevout_asset: self.prevout_asset,
+            prevout_value: self.prevout_value,
+            prevout_script_pubkey: self.prevout_script_pubkey,
+            genesis_hash: self.genesis_hash,
+            self_check: false,
+            key_path_keypair: self.key_path_keypair,
+            sibling_leaves: self.sibling_leaves,
+            extra_inputs: self.extra_inputs,
+            input_index: self.input_index,
+            extra_annex: self.extra_annex,
+            mangle_control_block: self.mangle_control_block,
         }
     }
 
     pub fn raw_cmr<A: AsRef<[u8]>>(self, cmr: A) -> TestBuilder<B, Cmr, E> {
         TestBuilder {
             comment: self.comment,
+            note: self.note,
+            expected_final_stack: self.expected_final_stack,
+            description: self.description,
             program_bytes: self.program_bytes,
             cmr: Cmr(cmr.as_ref().to_vec()),
             extra_script_inputs: self.extra_script_inputs,
             cost: self.cost,
+            padding_factor: self.padding_factor,
             error: self.error,
             skip_script_inputs: self.skip_script_inputs,
+            skip_control_block: self.skip_control_block,
+            control_block_internal_key: self.control_block_internal_key,
+            allow_cmr_mismatch: self.allow_cmr_mismatch,
+            cmr_check: self.cmr_check,
+            large_stack: self.large_stack,
+            leaf_version: self.leaf_version,
+            annex: self.annex,
+            sequence: self.sequence,
+            lock_time: self.lock_time,
+            outputs: self.outputs,
+            prevout_asset: self.prevout_asset,
+            prevout_value: self.prevout_value,
+            prevout_script_pubkey: self.prevout_script_pubkey,
+            genesis_hash: self.genesis_hash,
+            self_check: self.self_check,
+            key_path_keypair: self.key_path_keypair,
+            sibling_leaves: self.sibling_leaves,
+            extra_inputs: self.extra_inputs,
+            input_index: self.input_index,
+            extra_annex: self.extra_annex,
+            mangle_control_block: self.mangle_control_block,
         }
     }
 
@@ -87,12 +229,37 @@ impl<B: MaybeBytes, C: MaybeCmr, E: MaybeError> TestBuilder<B, C, E> {
     pub fn program(self, program: &RedeemNode<Elements>) -> TestBuilder<Bytes, Cmr, E> {
         TestBuilder {
             comment: self.comment,
+            note: self.note,
+            expected_final_stack: self.expected_final_stack,
+            description: self.description,
             program_bytes: Bytes(program.encode_to_vec()),
             cmr: Cmr(program.cmr().to_byte_array().to_vec()),
             extra_script_inputs: self.extra_script_inputs,
             cost: Some(program.bounds().cost),
+            padding_factor: self.padding_factor,
             error: self.error,
             skip_script_inputs: self.skip_script_inputs,
+            skip_control_block: self.skip_control_block,
+            control_block_internal_key: self.control_block_internal_key,
+            allow_cmr_mismatch: self.allow_cmr_mismatch,
+            cmr_check: self.cmr_check,
+            large_stack: self.large_stack,
+            leaf_version: self.leaf_version,
+            annex: self.annex,
+            sequence: self.sequence,
+            lock_time: self.lock_time,
+            outputs: self.outputs,
+            prevout_asset: self.prevout_asset,
+            prevout_value: self.prevout_value,
+            prevout_script_pubkey: self.prevout_script_pubkey,
+            genesis_hash: self.genesis_hash,
+            self_check: true,
+            key_path_keypair: self.key_path_keypair,
+            sibling_leaves: self.sibling_leaves,
+            extra_inputs: self.extra_inputs,
+            input_index: self.input_index,
+            extra_annex: self.extra_annex,
+            mangle_control_block: self.mangle_control_block,
         }
     }
 
@@ -110,11 +277,210 @@ impl<B: MaybeBytes, C: MaybeCmr, E: MaybeError> TestBuilder<B, C, E> {
         self
     }
 
+    /// Add `count` copies of `script_input` after the extra script inputs so far.
+    pub fn repeat_witness_element(mut self, script_input: Vec<u8>, count: usize) -> Self {
+        self.extra_script_inputs
+            .extend(std::iter::repeat_n(script_input, count));
+        self
+    }
+
     pub fn skip_script_inputs(mut self) -> Self {
         self.skip_script_inputs = true;
         self
     }
 
+    /// Leave out the control block, so the witness stack ends with the script.
+    pub fn skip_control_block(mut self) -> Self {
+        self.skip_control_block = true;
+        self
+    }
+
+    /// Serialize the control block with the given internal `key`
+    /// instead of the unspendable key that the output key commits to.
+    ///
+    /// Parity and merkle path stay the same, so only the internal key is wrong.
+    pub fn control_block_internal_key(mut self, key: secp256k1_zkp::XOnlyPublicKey) -> Self {
+        self.control_block_internal_key = Some(key);
+        self
+    }
+
+    /// Replace the serialized control block by the output of `mangle`
+    /// before it is pushed onto the witness stack.
+    pub fn mangle_control_block(mut self, mangle: fn(Vec<u8>) -> Vec<u8>) -> Self {
+        self.mangle_control_block = Some(mangle);
+        self
+    }
+
+    /// Allow the CMR to differ from the CMR of the program.
+    ///
+    /// By default, finishing the test case panics if the program decodes to a different CMR.
+    pub fn allow_cmr_mismatch(mut self) -> Self {
+        self.allow_cmr_mismatch = true;
+        self
+    }
+
+    /// Enable or disable the check that the program decodes to the CMR in the script.
+    ///
+    /// The check is enabled by default. Disable it for programs that take seconds to decode,
+    /// and for programs that the decoder rejects after type inference.
+    pub fn cmr_check(mut self, enabled: bool) -> Self {
+        self.cmr_check = enabled;
+        self
+    }
+
+    /// Decode the program on a thread with a large stack when checking its CMR.
+    ///
+    /// Call this method for programs whose types or nesting are so deep
+    /// that decoding them overflows the default stack.
+    pub fn large_stack(mut self) -> Self {
+        self.large_stack = true;
+        self
+    }
+
+    /// Commit to the leaf under the given `version` instead of the Simplicity leaf version.
+    ///
+    /// Leaves of other versions are not interpreted as Simplicity.
+    pub fn leaf_version(mut self, version: elements::taproot::LeafVersion) -> Self {
+        self.leaf_version = version;
+        self
+    }
+
+    /// Use the given `annex` instead of the padding that is computed from the program cost.
+    ///
+    /// The annex must start with 0x50 and it must be long enough to cover the program cost.
+    pub fn annex(mut self, annex: Vec<u8>) -> Self {
+        assert_eq!(Some(&0x50), annex.first(), "annex must start with 0x50");
+        assert!(self.extra_annex.is_none(), "extra annex is already set");
+        self.annex = Some(annex);
+        self
+    }
+
+    /// Push the given `annex` onto the witness stack, even if the program needs no padding.
+    ///
+    /// The annex must start with 0x50. If the program needs padding, then zero bytes are
+    /// appended to the annex until it covers the program cost, so there is only one annex.
+    pub fn extra_annex(mut self, annex: Vec<u8>) -> Self {
+        assert_eq!(Some(&0x50), annex.first(), "annex must start with 0x50");
+        assert!(self.annex.is_none(), "explicit annex is already set");
+        self.extra_annex = Some(annex);
+        self
+    }
+
+    /// Use the given `sequence` for the input of the spending transaction instead of the maximum.
+    pub fn sequence(mut self, sequence: elements::Sequence) -> Self {
+        self.sequence = sequence;
+        self
+    }
+
+    /// Use the given `lock_time` for the spending transaction instead of no lock time.
+    ///
+    /// The lock time is enforced only if the sequence of the input is not the maximum.
+    pub fn lock_time(mut self, lock_time: elements::LockTime) -> Self {
+        self.lock_time = lock_time;
+        self
+    }
+
+    /// Use the given `outputs` for the spending transaction instead of a single dummy output.
+    pub fn outputs(mut self, outputs: Vec<elements::TxOut>) -> Self {
+        self.outputs = outputs;
+        self
+    }
+
+    /// Use the given `asset` for the prevout that the spending transaction spends
+    /// instead of the null asset.
+    pub fn prevout_asset(mut self, asset: elements::confidential::Asset) -> Self {
+        self.prevout_asset = asset;
+        self
+    }
+
+    /// Use the given `asset` and `value` for the prevout that the spending transaction spends
+    /// instead of the null asset and the null value.
+    ///
+    /// If `script_pubkey_override` is given, then the prevout has this script pubkey
+    /// instead of the Taproot output of the Simplicity leaf.
+    /// The witness stack no longer matches the prevout unless the override commits
+    /// to the same Taproot output.
+    pub fn prevout(
+        mut self,
+        asset: elements::confidential::Asset,
+        value: elements::confidential::Value,
+        script_pubkey_override: Option<elements::Script>,
+    ) -> Self {
+        self.prevout_asset = asset;
+        self.prevout_value = value;
+        self.prevout_script_pubkey = script_pubkey_override;
+        self
+    }
+
+    /// Use the given genesis block `hash` instead of the default of the script assets tests,
+    /// which is all zeroes.
+    pub fn genesis_hash(mut self, hash: elements::BlockHash) -> Self {
+        self.genesis_hash = Some(hash);
+        self
+    }
+
+    /// Spend the Taproot output via the key path instead of the Simplicity leaf.
+    ///
+    /// The internal key of the output is the public key of `secret_key` instead of the
+    /// unspendable key. The tap tree still contains the Simplicity leaf.
+    /// The witness stack is a single signature, so the program is never decoded.
+    pub fn key_path_spend(mut self, secret_key: secp256k1_zkp::SecretKey) -> Self {
+        let keypair =
+            secp256k1_zkp::Keypair::from_secret_key(secp256k1_zkp::SECP256K1, &secret_key);
+        self.key_path_keypair = Some(keypair);
+        self
+    }
+
+    /// Add tapscript leaves with the given `scripts` to the tap tree, as (depth, script).
+    ///
+    /// The leaves follow the Simplicity leaf in depth-first order.
+    /// The Simplicity leaf has the depth of the first sibling, which is the length of
+    /// the Merkle path in its control block. See [`util::build_spend_info`].
+    pub fn sibling_leaves(mut self, scripts: Vec<(u8, Vec<u8>)>) -> Self {
+        self.sibling_leaves = scripts
+            .into_iter()
+            .map(|(depth, script)| (depth, elements::Script::from(script)))
+            .collect();
+        self
+    }
+
+    /// Add the given inputs to the spending transaction, each with the output that it spends.
+    ///
+    /// The inputs keep their order. The input that spends the Simplicity output
+    /// is inserted at the index that is set by [`TestBuilder::input_index`].
+    pub fn extra_inputs(mut self, inputs: Vec<(elements::TxIn, elements::TxOut)>) -> Self {
+        self.extra_inputs = inputs;
+        self
+    }
+
+    /// Place the input that spends the Simplicity output at the given `index`
+    /// instead of at index 0, among the inputs that are set by [`TestBuilder::extra_inputs`].
+    pub fn input_index(mut self, index: usize) -> Self {
+        self.input_index = index;
+        self
+    }
+
+    /// Enable or disable the check of the expected error against rust-simplicity.
+    ///
+    /// The check is enabled by [`TestBuilder::program`] and disabled by [`TestBuilder::raw_program`],
+    /// so call this method after setting the program.
+    /// Only errors of the execution phase are checked, see [`self_check`].
+    pub fn self_check(mut self, enabled: bool) -> Self {
+        self.self_check = enabled;
+        self
+    }
+
+    /// Scale the padding that is computed from the program cost by the factor `f`.
+    ///
+    /// The number of zero bytes after the leading 0x50 byte of the annex is multiplied by `f`
+    /// and rounded to the nearest integer. A factor below 1.0 leaves the budget insufficient.
+    /// The factor has no effect on an explicit annex or if the program needs no padding.
+    pub fn padding_factor(mut self, f: f64) -> Self {
+        assert!(0.0 <= f, "padding factor must not be negative");
+        self.padding_factor = f;
+        self
+    }
+
     pub fn reset_cost(mut self) -> Self {
         self.cost = None;
         self
@@ -123,110 +489,497 @@ impl<B: MaybeBytes, C: MaybeCmr, E: MaybeError> TestBuilder<B, C, E> {
     pub fn expected_error(self, error: ScriptError) -> TestBuilder<B, C, Error> {
         TestBuilder {
             comment: self.comment,
+            note: self.note,
+            expected_final_stack: self.expected_final_stack,
+            description: self.description,
             program_bytes: self.program_bytes,
             cmr: self.cmr,
             extra_script_inputs: self.extra_script_inputs,
             cost: self.cost,
+            padding_factor: self.padding_factor,
             error: Error(error),
             skip_script_inputs: self.skip_script_inputs,
+            skip_control_block: self.skip_control_block,
+            control_block_internal_key: self.control_block_internal_key,
+            allow_cmr_mismatch: self.allow_cmr_mismatch,
+            cmr_check: self.cmr_check,
+            large_stack: self.large_stack,
+            leaf_version: self.leaf_version,
+            annex: self.annex,
+            sequence: self.sequence,
+            lock_time: self.lock_time,
+            outputs: self.outputs,
+            prevout_asset: self.prevout_asset,
+            prevout_value: self.prevout_value,
+            prevout_script_pubkey: self.prevout_script_pubkey,
+            genesis_hash: self.genesis_hash,
+            self_check: self.self_check,
+            key_path_keypair: self.key_path_keypair,
+            sibling_leaves: self.sibling_leaves,
+            extra_inputs: self.extra_inputs,
+            input_index: self.input_index,
+            extra_annex: self.extra_annex,
+            mangle_control_block: self.mangle_control_block,
         }
     }
 }
 
 impl TestBuilder<Bytes, Cmr, Error> {
     pub fn finished(self) -> TestCase {
-        let program_bytes = self.program_bytes.0;
-        let cmr = self.cmr.0;
+        self.assert_consistent_cmr();
         let error = match self.error.0 {
             ScriptError::Ok => None,
             error => Some(error),
         };
 
-        let spend_info = util::get_spend_info(cmr.clone(), simplicity::leaf_version());
-        let control_block =
-            util::get_control_block(cmr.clone(), simplicity::leaf_version(), &spend_info).unwrap();
+        let witness = match self.key_path_keypair {
+            Some(keypair) => vec![self.get_key_path_signature(&keypair)],
+            None => {
+                let mut witness = self.get_witness();
+                if let Some(annex) = self.get_annex(&witness) {
+                    witness.push(annex);
+                }
+                witness
+            }
+        };
+        print_budget(&witness);
+
+        let parameters = Parameters::taproot(witness, error);
+        let (success, failure) = match error {
+            None => (Some(parameters), None),
+            Some(_) => (None, Some(parameters)),
+        };
+
+        let enabled = self.self_check;
+        let test_case = self.get_test_case(success, failure);
+        if enabled {
+            self_check(&test_case);
+        }
+        test_case
+    }
+
+    /// Finish a test case whose success and failure arms differ only in the annex.
+    ///
+    /// The success arm uses the padding that is computed from the program cost.
+    /// The failure arm uses `failure_annex` instead and is expected to fail with the expected error.
+    pub fn finished_annex_pair(self, failure_annex: Option<Vec<u8>>) -> TestCase {
+        self.assert_consistent_cmr();
+        let error = self.error.0;
+        assert_ne!(ScriptError::Ok, error, "failure arm must fail");
+
+        let mut success_witness = self.get_witness();
+        if let Some(annex) = self.get_annex(&success_witness) {
+            success_witness.push(annex);
+        }
+        print_budget(&success_witness);
+
+        let mut failure_witness = self.get_witness();
+        if let Some(annex) = failure_annex {
+            failure_witness.push(annex);
+        }
+        print_budget(&failure_witness);
+
+        let success = Parameters::taproot(success_witness, None);
+        let failure = Parameters::taproot(failure_witness, Some(error));
+
+        let enabled = self.self_check;
+        let test_case = self.get_test_case(Some(success), Some(failure));
+        if enabled {
+            self_check(&test_case);
+        }
+        test_case
+    }
+
+    /// Return whether the witness stack without annex is too small to cover the program cost.
+    pub fn needs_padding(&self) -> bool {
+        let witness = self.get_witness();
+        self.cost
+            .is_some_and(|cost| cost.get_padding(&witness).is_some())
+    }
+
+    /// Panic if the program decodes to a different CMR than the one committed in the script.
+    ///
+    /// Spends without Simplicity are not checked.
+    /// Programs that do not decode are only accepted if the expected error is raised
+    /// before the end of type inference, which the decoder runs.
+    /// The check is skipped after [`TestBuilder::allow_cmr_mismatch`] or `cmr_check(false)`.
+    fn assert_consistent_cmr(&self) {
+        let comment = &self.comment;
+        if self.allow_cmr_mismatch || self.key_path_keypair.is_some() {
+            return;
+        }
+        if self.leaf_version != simplicity::leaf_version() {
+            return;
+        }
+        if !self.cmr_check {
+            if is_verbose() {
+                println!("{comment}: Skip CMR check");
+            }
+            return;
+        }
+        let program_bytes = self.program_bytes.0.clone();
+        let decoded = if self.large_stack {
+            util::on_large_stack(move || util::decode_cmr(program_bytes))
+        } else {
+            util::decode_cmr(program_bytes)
+        };
+        match decoded {
+            Some(program_cmr) => assert_eq!(
+                program_cmr.as_ref(),
+                self.cmr.0.as_slice(),
+                "{comment}: program has a different CMR than the script"
+            ),
+            None => {
+                let error = self.error.0;
+                assert!(
+                    error.phase().is_some_and(|phase| phase <= Phase::TypeCheck),
+                    "{comment}: program does not decode, but {error} is raised after type inference"
+                );
+                if is_verbose() {
+                    println!("{comment}: Skip CMR check of a program that does not decode");
+                }
+            }
+        }
+    }
 
-        let funding_tx = get_funding_tx(&spend_info);
-        let spending_tx = get_spending_tx(&funding_tx);
+    /// Return the witness stack without annex.
+    fn get_witness(&self) -> Vec<Vec<u8>> {
+        let cmr = self.cmr.0.clone();
+        let spend_info = self.get_spend_info();
+        let mut control_block =
+            util::get_control_block(cmr.clone(), self.leaf_version, &spend_info)
+                .unwrap_or_else(|error| panic!("{}", error));
+        if let Some(key) = self.control_block_internal_key {
+            control_block.internal_key = key;
+        }
 
         let script_inputs = if self.skip_script_inputs {
             vec![]
         } else {
-            let mut script_inputs = vec![program_bytes];
-            script_inputs.extend(self.extra_script_inputs);
+            let mut script_inputs = vec![self.program_bytes.0.clone()];
+            script_inputs.extend(self.extra_script_inputs.iter().cloned());
             script_inputs
         };
         let script = util::to_script(cmr);
         let mut witness = util::get_witness_stack(script_inputs, script, control_block);
+        if let Some(mangle) = self.mangle_control_block {
+            let control_block = witness
+                .pop()
+                .expect("witness stack ends with control block");
+            witness.push(mangle(control_block));
+        }
+        if self.skip_control_block {
+            witness.pop();
+        }
+        witness
+    }
 
-        if let Some(cost) = self.cost {
-            if let Some(annex) = cost.get_padding(&witness) {
-                witness.push(annex);
-            }
+    /// Return the internal key of the Taproot output.
+    fn get_internal_key(&self) -> secp256k1_zkp::XOnlyPublicKey {
+        match self.key_path_keypair {
+            Some(keypair) => keypair.x_only_public_key().0,
+            None => util::unspendable_key(),
         }
+    }
 
-        let mut sink = std::io::sink();
-        let budget = elements::encode::Encodable::consensus_encode(&witness, &mut sink).unwrap();
-        let budget = u32::try_from(budget).expect("too many bytes");
-        let milliseconds_per_wu = 0.5 / 1_000.0;
-        let max_milliseconds = f64::from(budget) * milliseconds_per_wu;
-        println!(
-            "Budget = {} WU. Should take less than {:.3}ms",
-            budget, max_milliseconds
-        );
+    /// Return the spending information of the Taproot output.
+    fn get_spend_info(&self) -> elements::taproot::TaprootSpendInfo {
+        util::build_spend_info(
+            &self.cmr.0,
+            self.leaf_version,
+            self.get_internal_key(),
+            &self.sibling_leaves,
+        )
+    }
 
-        let parameters = Parameters::taproot(witness, error);
-        let (success, failure) = match error {
-            None => (Some(parameters), None),
-            Some(_) => (None, Some(parameters)),
+    /// Return the transaction, its prevouts and the index of the spending input.
+    fn get_spend(&self) -> (elements::Transaction, Vec<elements::TxOut>, usize) {
+        let spend = util::Spend {
+            sequence: self.sequence,
+            lock_time: self.lock_time,
+            outputs: self.outputs.clone(),
+            prevout_asset: self.prevout_asset,
+            prevout_value: self.prevout_value,
+            prevout_script_pubkey: self.prevout_script_pubkey.clone(),
+            extra_inputs: self.extra_inputs.clone(),
+            input_index: self.input_index,
         };
+        util::spend_output(&self.get_spend_info(), spend)
+    }
+
+    /// Return the signature of a key path spend with the given `keypair`.
+    fn get_key_path_signature(&self, keypair: &secp256k1_zkp::Keypair) -> Vec<u8> {
+        let (tx, prevouts, index) = self.get_spend();
+        let spend_info = self.get_spend_info();
+        // The script assets tests default to a genesis block hash of all zeroes
+        let genesis_hash = self
+            .genesis_hash
+            .unwrap_or(elements::BlockHash::all_zeros());
+        util::key_path_signature(
+            &tx,
+            &prevouts,
+            index,
+            keypair,
+            spend_info.merkle_root(),
+            genesis_hash,
+        )
+    }
+
+    /// Return the annex of the given witness stack.
+    ///
+    /// This is either the explicit annex, the padding that covers the program cost,
+    /// or the extra annex that is extended by the padding.
+    fn get_annex(&self, witness: &Vec<Vec<u8>>) -> Option<Vec<u8>> {
+        if let Some(annex) = &self.annex {
+            return Some(annex.clone());
+        }
+        let padding = self
+            .cost
+            .and_then(|cost| cost.get_padding(witness))
+            .map(|mut padding| {
+                let n_zeroes = (padding.len() - 1) as f64 * self.padding_factor;
+                padding.resize(1 + n_zeroes.round() as usize, 0x00);
+                padding
+            });
+        match (&self.extra_annex, padding) {
+            (Some(extra_annex), Some(padding)) => {
+                let mut annex = extra_annex.clone();
+                annex.resize(annex.len().max(padding.len()), 0x00);
+                Some(annex)
+            }
+            (Some(extra_annex), None) => Some(extra_annex.clone()),
+            (None, padding) => padding,
+        }
+    }
+
+    fn get_test_case(self, success: Option<Parameters>, failure: Option<Parameters>) -> TestCase {
+        let (spending_tx, prevouts, index) = self.get_spend();
 
         TestCase {
             tx: Serde(spending_tx),
-            prevouts: funding_tx.output.into_iter().map(Serde).collect(),
-            index: 0,
+            prevouts: prevouts.into_iter().map(Serde).collect(),
+            index,
             flags: Flag::all_flags().to_vec(),
             comment: self.comment.to_string(),
-            hash_genesis_block: None,
+            note: self.note,
+            description: self.description,
+            hash_genesis_block: self.genesis_hash,
             success,
             failure,
             is_final: false,
+            expected_final_stack: self
+                .expected_final_stack
+                .map(|stack| stack.into_iter().map(Serde).collect()),
         }
     }
 }
 
-fn get_funding_tx(spend_info: &elements::taproot::TaprootSpendInfo) -> elements::Transaction {
-    let coinbase = elements::TxIn::default();
-    let output = elements::TxOut {
-        asset: elements::confidential::Asset::Null,
-        value: elements::confidential::Value::Null,
-        nonce: elements::confidential::Nonce::Null,
-        script_pubkey: util::get_script_pubkey(spend_info),
-        // The witness is overwritten by script_tests.cpp based on the success / failure parameters
-        witness: elements::TxOutWitness::default(),
-    };
-    elements::Transaction {
-        version: 2,
-        lock_time: elements::LockTime::ZERO,
-        input: vec![coinbase],
-        output: vec![output],
+/// Panic if rust-simplicity raises a different error than expected by any arm of the test case.
+///
+/// Only the outcomes of the execution phase are checked: success, jet failure, failed assertion
+/// and exceeded budget. The other errors are checked by the C implementation only,
+/// because rust-simplicity checks less or in a different order, so these arms are skipped.
+/// Arms that spend without Simplicity are skipped, too.
+fn self_check(test_case: &TestCase) {
+    let comment = &test_case.comment;
+    let arms = [&test_case.success, &test_case.failure];
+    for parameters in arms.into_iter().flatten() {
+        let expected = parameters.error.unwrap_or(ScriptError::Ok);
+        if !matches!(
+            expected,
+            ScriptError::Ok
+                | ScriptError::SimplicityExecJet
+                | ScriptError::SimplicityExecAssert
+                | ScriptError::SimplicityExecBudget
+        ) {
+            if is_verbose() {
+                println!("{comment}: Skip self-check of {expected}, which only C checks");
+            }
+            continue;
+        }
+
+        let script_witness: Vec<Vec<u8>> = parameters.witness.iter().map(|x| x.0.clone()).collect();
+        let mut witness = script_witness.clone();
+        if 2 <= witness.len() && witness.last().and_then(|x| x.first()) == Some(&0x50) {
+            witness.pop();
+        }
+        let [program_bytes, cmr, control_block] = witness.as_slice() else {
+            if is_verbose() {
+                println!("{comment}: Skip self-check of a spend without Simplicity");
+            }
+            continue;
+        };
+        if control_block.first().map(|byte| byte & 0xfe) != Some(simplicity::leaf_version().as_u8())
+        {
+            if is_verbose() {
+                println!("{comment}: Skip self-check of a spend without Simplicity");
+            }
+            continue;
+        }
+
+        let mut bits = BitIter::from(program_bytes.iter().copied());
+        let program = RedeemNode::<Elements>::decode(&mut bits)
+            .unwrap_or_else(|error| panic!("{comment}: Program does not decode: {error}"));
+        let cost = program.bounds().cost;
+        let actual = if !cost.is_consensus_valid() || !cost.is_budget_valid(&script_witness) {
+            ScriptError::SimplicityExecBudget
+        } else {
+            let prevouts: Vec<elements::TxOut> =
+                test_case.prevouts.iter().map(|x| x.0.clone()).collect();
+            let cmr =
+                simplicity::Cmr::from_byte_array(cmr.as_slice().try_into().expect("32-byte CMR"));
+            // The script assets tests default to a genesis block hash of all zeroes
+            let genesis_hash = test_case
+                .hash_genesis_block
+                .unwrap_or(elements::BlockHash::all_zeros());
+            let env = util::elements_env(
+                Arc::new(test_case.tx.0.clone()),
+                &prevouts,
+                test_case.index,
+                cmr,
+                control_block,
+                genesis_hash,
+            );
+            let mut mac = BitMachine::for_program(&program);
+            match mac.exec(&program, &env) {
+                Ok(_) => ScriptError::Ok,
+                Err(ExecutionError::JetFailed(_)) => ScriptError::SimplicityExecJet,
+                Err(ExecutionError::ReachedPrunedBranch(_)) => ScriptError::SimplicityExecAssert,
+                Err(ExecutionError::ReachedFailNode(_)) => ScriptError::SimplicityFailCode,
+            }
+        };
+        assert_eq!(
+            expected, actual,
+            "{comment}: rust-simplicity raises {actual} instead of {expected}"
+        );
+    }
+}
+
+fn print_budget(witness: &Vec<Vec<u8>>) {
+    if !is_verbose() {
+        return;
     }
+    let mut sink = std::io::sink();
+    let budget = elements::encode::Encodable::consensus_encode(witness, &mut sink).unwrap();
+    let budget = u32::try_from(budget).expect("too many bytes");
+    let milliseconds_per_wu = 0.5 / 1_000.0;
+    let max_milliseconds = f64::from(budget) * milliseconds_per_wu;
+    println!(
+        "Budget = {} WU. Should take less than {:.3}ms",
+        budget, max_milliseconds
+    );
 }
 
-fn get_spending_tx(funding_tx: &elements::Transaction) -> elements::Transaction {
-    let input = elements::TxIn {
-        previous_output: util::to_outpoint(funding_tx),
-        is_pegin: false,
-        script_sig: elements::Script::new(),
-        sequence: elements::Sequence::MAX,
-        asset_issuance: elements::AssetIssuance::default(),
-        witness: elements::TxInWitness::default(),
-    };
-    let dummy = elements::TxOut::default();
-    elements::Transaction {
-        version: 2,
-        lock_time: elements::LockTime::ZERO,
-        input: vec![input],
-        output: vec![dummy],
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[should_panic(expected = "program has a different CMR than the script")]
+    fn cmr_mismatch_panics() {
+        TestBuilder::comment("cmr/mismatch")
+            .human_encoding("main := unit", &HashMap::new())
+            .raw_cmr(simplicity::Cmr::iden())
+            .expected_error(ScriptError::SimplicityCmr)
+            .finished();
+    }
+
+    #[test]
+    #[should_panic(expected = "program does not decode, but SIMPLICITY_EXEC_JET is raised")]
+    fn undecodable_program_panics() {
+        TestBuilder::comment("exec_jet/empty_program")
+            .raw_program(vec![])
+            .raw_cmr(simplicity::Cmr::unit())
+            .expected_error(ScriptError::SimplicityExecJet)
+            .finished();
+    }
+
+    #[test]
+    #[should_panic(expected = "rust-simplicity raises OK instead of SIMPLICITY_EXEC_JET")]
+    fn self_check_panics() {
+        TestBuilder::comment("exec_jet/unit")
+            .human_encoding("main := unit", &HashMap::new())
+            .expected_error(ScriptError::SimplicityExecJet)
+            .finished();
+    }
+
+    #[test]
+    fn self_check_disabled() {
+        TestBuilder::comment("exec_jet/unit")
+            .human_encoding("main := unit", &HashMap::new())
+            .self_check(false)
+            .expected_error(ScriptError::SimplicityExecJet)
+            .finished();
+    }
+
+    #[test]
+    fn extra_input_before_spending_input() {
+        let dummy_input = elements::TxIn {
+            previous_output: elements::OutPoint::new(elements::Txid::all_zeros(), 1),
+            ..Default::default()
+        };
+        let test_case = TestBuilder::comment("ok/unit")
+            .human_encoding("main := unit", &HashMap::new())
+            .extra_inputs(vec![(dummy_input.clone(), elements::TxOut::default())])
+            .input_index(1)
+            .expected_error(ScriptError::Ok)
+            .finished();
+        assert_eq!(1, test_case.index);
+        assert_eq!(2, test_case.tx.0.input.len());
+        assert_eq!(2, test_case.prevouts.len());
+        assert_eq!(dummy_input, test_case.tx.0.input[0]);
+        assert_eq!(elements::TxOut::default(), test_case.prevouts[0].0);
+    }
+
+    #[test]
+    fn extra_annex_is_extended_by_padding() {
+        // Composition ladder of depth 9, which needs padding
+        let mut s = String::from("c0 := comp iden iden\n");
+        for depth in 1..10 {
+            let child = depth - 1;
+            s.push_str(&format!("c{depth} := comp c{child} c{child}\n"));
+        }
+        s.push_str("main := c9\n");
+        let program = util::program_from_string(&s, &HashMap::new());
+        let extra_annex = vec![0x50, 0xca, 0xfe];
+
+        let padded = TestBuilder::comment("ok/padded")
+            .program(&program)
+            .expected_error(ScriptError::Ok)
+            .finished();
+        let test_case = TestBuilder::comment("ok/extra_annex")
+            .program(&program)
+            .extra_annex(extra_annex.clone())
+            .expected_error(ScriptError::Ok)
+            .finished();
+
+        let padded_witness = &padded.success.unwrap().witness;
+        let witness = &test_case.success.unwrap().witness;
+        assert_eq!(4, witness.len());
+        let annex = &witness[3].0;
+        assert_eq!(extra_annex, annex[..extra_annex.len()]);
+        assert_eq!(padded_witness[3].0.len(), annex.len());
+    }
+
+    #[test]
+    #[should_panic(expected = "explicit annex is already set")]
+    fn extra_annex_after_annex_panics() {
+        TestBuilder::comment("ok/unit")
+            .annex(vec![0x50])
+            .extra_annex(vec![0x50]);
+    }
+
+    #[test]
+    fn allow_cmr_mismatch() {
+        let test_case = TestBuilder::comment("cmr/mismatch")
+            .human_encoding("main := unit", &HashMap::new())
+            .raw_cmr(simplicity::Cmr::iden())
+            .allow_cmr_mismatch()
+            .expected_error(ScriptError::SimplicityCmr)
+            .finished();
+        let (_, cmr) = test_case
+            .program_cmr()
+            .expect("witness has program and CMR");
+        assert_eq!(simplicity::Cmr::iden().as_ref(), cmr);
     }
 }
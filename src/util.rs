@@ -5,15 +5,26 @@
 //! This lets us manipulate the spending process more freely and it lets us provoke errors.
 
 use std::borrow::Cow;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Write as _;
 use std::sync::Arc;
 use std::{fmt, io};
 
+use elements::hashes::Hash as _;
+use elements::hex::ToHex;
+use elements::schnorr::TapTweak as _;
 use elements::secp256k1_zkp;
 use elements_miniscript as miniscript;
 use miniscript::{bitcoin, elements};
-use simplicity::jet::Elements;
-use simplicity::{BitWriter, RedeemNode, WitnessNode};
+use simplicity::dag::{DagLike, InternalSharing, PostOrderIterItem};
+use simplicity::jet::elements::{ElementsEnv, ElementsUtxo};
+use simplicity::jet::type_name::TypeName;
+use simplicity::jet::{Elements, Jet};
+use simplicity::node::{
+    Commit, CommitData, CommitNode, Converter, CoreConstructible, Inner, NoDisconnect, NoWitness,
+    Witness,
+};
+use simplicity::{BitIter, BitWriter, Cmr, ConstructNode, RedeemNode, WitnessNode};
 
 /// Nothing-up-my-sleeve point.
 ///
@@ -36,6 +47,13 @@ pub fn to_script<A: AsRef<[u8]>>(bytes: A) -> elements::Script {
     elements::Script::from(bytes.as_ref().to_vec())
 }
 
+/// Convert the given Elements Script back into its byte structure.
+///
+/// This is the inverse of [`to_script()`].
+pub fn from_script(script: &elements::Script) -> Vec<u8> {
+    script.to_bytes()
+}
+
 /// Return a constant x-only public key with unknown discrete logarithm.
 ///
 /// The key is therefore unspendable.
@@ -50,36 +68,322 @@ pub fn unspendable_key() -> bitcoin::key::XOnlyPublicKey {
 pub fn get_spend_info<A: AsRef<[u8]>>(
     commit: A,
     version: elements::taproot::LeafVersion,
+) -> elements::taproot::TaprootSpendInfo {
+    build_spend_info(commit, version, unspendable_key(), &[])
+}
+
+/// Compute Taproot spending information about an output with
+///
+/// 1. The given `internal_key`
+/// 2. A tap tree whose first leaf has the given `version` and contains `commit`,
+///    followed by tapscript leaves with the given `siblings` as (depth, script), in depth-first order.
+///
+/// The first leaf has the depth of the first sibling, or depth 0 if there are no siblings.
+///
+/// ## Panics
+///
+/// The depths do not describe a tree.
+pub fn build_spend_info<A: AsRef<[u8]>>(
+    commit: A,
+    version: elements::taproot::LeafVersion,
+    internal_key: secp256k1_zkp::XOnlyPublicKey,
+    siblings: &[(u8, elements::Script)],
 ) -> elements::taproot::TaprootSpendInfo {
     let script = to_script(commit);
-    elements::taproot::TaprootBuilder::new()
-        .add_leaf_with_ver(0, script, version)
-        .expect("const")
-        .finalize(secp256k1_zkp::SECP256K1, unspendable_key())
-        .expect("const")
+    let depth = siblings.first().map_or(0, |(depth, _)| *depth);
+    let mut builder = elements::taproot::TaprootBuilder::new()
+        .add_leaf_with_ver(usize::from(depth), script, version)
+        .expect("valid depth");
+    for (depth, script) in siblings {
+        builder = builder
+            .add_leaf(usize::from(*depth), script.clone())
+            .expect("valid depth");
+    }
+    builder
+        .finalize(secp256k1_zkp::SECP256K1, internal_key)
+        .expect("depths describe a tree")
+}
+
+/// Compute the output key of the Taproot output with the given spending information.
+pub fn taproot_output_key(
+    spend_info: &elements::taproot::TaprootSpendInfo,
+) -> secp256k1_zkp::XOnlyPublicKey {
+    spend_info.output_key().into_inner()
 }
 
 /// Compute the `script_pubkey` of the Taproot output with the given spending information.
 pub fn get_script_pubkey(spend_info: &elements::taproot::TaprootSpendInfo) -> elements::Script {
-    let output_key = spend_info.output_key();
+    let output_key = taproot_output_key(spend_info);
     let builder = elements::script::Builder::new();
     builder
         .push_opcode(elements::opcodes::all::OP_PUSHNUM_1)
-        .push_slice(&output_key.as_inner().serialize())
+        .push_slice(&output_key.serialize())
         .into_script()
 }
 
+/// Return a transaction that spends the Taproot output which commits to `cmr`,
+/// together with its prevouts and the index of the spending input.
+///
+/// The Taproot output uses the Simplicity leaf version.
+/// The spending input has the maximum sequence number and the transaction has a single dummy output.
+/// The transaction has no lock time.
+pub fn minimal_spend_for_cmr<A: AsRef<[u8]>>(
+    cmr: A,
+) -> (elements::Transaction, Vec<elements::TxOut>, usize) {
+    spend_output(
+        &get_spend_info(cmr, simplicity::leaf_version()),
+        Spend::default(),
+    )
+}
+
+/// Parameters of a transaction that spends a Taproot output.
+///
+/// The default is a single input with the maximum sequence number, no lock time
+/// and a single dummy output. The spent output has a null asset and a null value.
+#[derive(Debug, Clone)]
+pub struct Spend {
+    /// Sequence of the spending input.
+    pub sequence: elements::Sequence,
+    /// Lock time of the transaction.
+    pub lock_time: elements::LockTime,
+    /// Outputs of the transaction.
+    pub outputs: Vec<elements::TxOut>,
+    /// Asset of the spent output.
+    pub prevout_asset: elements::confidential::Asset,
+    /// Value of the spent output.
+    pub prevout_value: elements::confidential::Value,
+    /// Script pubkey of the spent output instead of the one of the Taproot output.
+    pub prevout_script_pubkey: Option<elements::Script>,
+    /// Other inputs of the transaction, each with the output that it spends.
+    pub extra_inputs: Vec<(elements::TxIn, elements::TxOut)>,
+    /// Index of the spending input among the extra inputs.
+    pub input_index: usize,
+}
+
+impl Default for Spend {
+    fn default() -> Self {
+        Self {
+            sequence: elements::Sequence::MAX,
+            lock_time: elements::LockTime::ZERO,
+            outputs: vec![elements::TxOut::default()],
+            prevout_asset: elements::confidential::Asset::Null,
+            prevout_value: elements::confidential::Value::Null,
+            prevout_script_pubkey: None,
+            extra_inputs: vec![],
+            input_index: 0,
+        }
+    }
+}
+
+/// Return a transaction that spends the Taproot output with the given spending information,
+/// together with its prevouts and the index of the spending input.
+///
+/// The transaction is described by `spend`.
+/// The spending input is inserted among the extra inputs at the input index.
+///
+/// ## Panics
+///
+/// The input index is greater than the number of extra inputs.
+pub fn spend_output(
+    spend_info: &elements::taproot::TaprootSpendInfo,
+    spend: Spend,
+) -> (elements::Transaction, Vec<elements::TxOut>, usize) {
+    let index = spend.input_index;
+    assert!(
+        index <= spend.extra_inputs.len(),
+        "Input index {index} is out of range for {} extra inputs",
+        spend.extra_inputs.len()
+    );
+    let script_pubkey = spend
+        .prevout_script_pubkey
+        .unwrap_or_else(|| get_script_pubkey(spend_info));
+    let funding_tx = get_funding_tx(spend.prevout_asset, spend.prevout_value, script_pubkey);
+    let mut spending_tx =
+        get_spending_tx(&funding_tx, spend.sequence, spend.lock_time, spend.outputs);
+    let spending_input = spending_tx.input.pop().expect("spending tx has one input");
+    let (mut inputs, mut prevouts): (Vec<_>, Vec<_>) = spend.extra_inputs.into_iter().unzip();
+    inputs.insert(index, spending_input);
+    prevouts.insert(index, funding_tx.output[0].clone());
+    spending_tx.input = inputs;
+
+    (spending_tx, prevouts, index)
+}
+
+fn get_funding_tx(
+    asset: elements::confidential::Asset,
+    value: elements::confidential::Value,
+    script_pubkey: elements::Script,
+) -> elements::Transaction {
+    let coinbase = elements::TxIn::default();
+    let output = elements::TxOut {
+        asset,
+        value,
+        nonce: elements::confidential::Nonce::Null,
+        script_pubkey,
+        // The witness is overwritten by script_tests.cpp based on the success / failure parameters
+        witness: elements::TxOutWitness::default(),
+    };
+    elements::Transaction {
+        version: 2,
+        lock_time: elements::LockTime::ZERO,
+        input: vec![coinbase],
+        output: vec![output],
+    }
+}
+
+fn get_spending_tx(
+    funding_tx: &elements::Transaction,
+    sequence: elements::Sequence,
+    lock_time: elements::LockTime,
+    outputs: Vec<elements::TxOut>,
+) -> elements::Transaction {
+    let input = elements::TxIn {
+        previous_output: to_outpoint(funding_tx),
+        is_pegin: false,
+        script_sig: elements::Script::new(),
+        sequence,
+        asset_issuance: elements::AssetIssuance::default(),
+        witness: elements::TxInWitness::default(),
+    };
+    elements::Transaction {
+        version: 2,
+        lock_time,
+        input: vec![input],
+        output: outputs,
+    }
+}
+
+/// Return the message that `jet_sig_all_hash` computes for the given spend.
+///
+/// The spend must not have an annex: It would be part of the message,
+/// but the Rust environment does not pass the annex on to the C jets.
+pub fn sighash_all(
+    tx: &elements::Transaction,
+    prevouts: &[elements::TxOut],
+    index: usize,
+    cmr: Cmr,
+    control_block: &[u8],
+    genesis_hash: elements::BlockHash,
+) -> [u8; 32] {
+    let env = elements_env(
+        Arc::new(tx.clone()),
+        prevouts,
+        index,
+        cmr,
+        control_block,
+        genesis_hash,
+    );
+    env.c_tx_env().sighash_all().to_byte_array()
+}
+
+/// Return the BIP 341 signature of a key path spend of input `index` of `tx`,
+/// which spends `prevouts` on the chain with the given `genesis_hash`.
+///
+/// The output key is `keypair` tweaked with the `merkle_root` of the tap tree.
+/// The signature uses the default sighash type, so it is 64 bytes long.
+pub fn key_path_signature(
+    tx: &elements::Transaction,
+    prevouts: &[elements::TxOut],
+    index: usize,
+    keypair: &secp256k1_zkp::Keypair,
+    merkle_root: Option<elements::taproot::TapNodeHash>,
+    genesis_hash: elements::BlockHash,
+) -> Vec<u8> {
+    let sighash = elements::sighash::SighashCache::new(tx)
+        .taproot_key_spend_signature_hash(
+            index,
+            &elements::sighash::Prevouts::All(prevouts),
+            elements::SchnorrSighashType::Default,
+            genesis_hash,
+        )
+        .expect("input index exists");
+    let message = secp256k1_zkp::Message::from_digest(sighash.to_byte_array());
+    let tweaked = keypair.tap_tweak(secp256k1_zkp::SECP256K1, merkle_root);
+    secp256k1_zkp::SECP256K1
+        .sign_schnorr_no_aux_rand(&message, &tweaked.to_inner())
+        .serialize()
+        .to_vec()
+}
+
+/// Return the environment in which the program under `cmr` is executed
+/// when input `index` of `tx` spends `prevouts` on the chain with the given `genesis_hash`,
+/// without annex.
+pub fn elements_env(
+    tx: Arc<elements::Transaction>,
+    prevouts: &[elements::TxOut],
+    index: usize,
+    cmr: Cmr,
+    control_block: &[u8],
+    genesis_hash: elements::BlockHash,
+) -> ElementsEnv<Arc<elements::Transaction>> {
+    let utxos = prevouts
+        .iter()
+        .map(|prevout| ElementsUtxo {
+            script_pubkey: prevout.script_pubkey.clone(),
+            asset: prevout.asset,
+            value: prevout.value,
+        })
+        .collect();
+    let control_block =
+        elements::taproot::ControlBlock::from_slice(control_block).expect("valid control block");
+    ElementsEnv::new(
+        tx,
+        utxos,
+        u32::try_from(index).expect("index fits into u32"),
+        cmr,
+        control_block,
+        None,
+        genesis_hash,
+    )
+}
+
+/// The tap tree has no leaf of the given `version` that contains `script`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct NoLeafError {
+    script: elements::Script,
+    version: elements::taproot::LeafVersion,
+    internal_key: secp256k1_zkp::XOnlyPublicKey,
+    merkle_root: Option<elements::taproot::TapNodeHash>,
+}
+
+impl fmt::Display for NoLeafError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "No leaf of version 0x{:02x} contains script {} (internal key {}, merkle root ",
+            self.version.as_u8(),
+            self.script.as_bytes().to_hex(),
+            self.internal_key,
+        )?;
+        match self.merkle_root {
+            Some(merkle_root) => write!(f, "{})", merkle_root),
+            None => f.write_str("none)"),
+        }
+    }
+}
+
+impl std::error::Error for NoLeafError {}
+
 /// Compute a control block of the Taproot output with the given spending information.
 ///
-/// The control block selects the leaf of the given `version` that contains `commit`, if it exists.
+/// The control block selects the leaf of the given `version` that contains `commit`.
+///
+/// Return an error that describes the tap tree if there is no such leaf.
 pub fn get_control_block<A: AsRef<[u8]>>(
     commit: A,
     version: elements::taproot::LeafVersion,
     spend_info: &elements::taproot::TaprootSpendInfo,
-) -> Option<elements::taproot::ControlBlock> {
+) -> Result<elements::taproot::ControlBlock, NoLeafError> {
     let script = to_script(commit);
     let script_ver = (script, version);
-    spend_info.control_block(&script_ver)
+    spend_info
+        .control_block(&script_ver)
+        .ok_or_else(|| NoLeafError {
+            script: script_ver.0,
+            version,
+            internal_key: spend_info.internal_key(),
+            merkle_root: spend_info.merkle_root(),
+        })
 }
 
 pub fn get_witness_stack(
@@ -105,6 +409,149 @@ pub fn program_from_string(
         .expect("well-typed and sufficient witness")
 }
 
+/// Return a copy of `bytes` where the bit at `bit_index` is flipped.
+///
+/// Bits are indexed in the order of decoding, starting at the most significant bit of the first byte.
+pub fn corrupt_at(bytes: &[u8], bit_index: usize) -> Vec<u8> {
+    assert!(
+        bit_index < bytes.len() * 8,
+        "Bit index points past end of bytes"
+    );
+    let mut bytes = bytes.to_vec();
+    bytes[bit_index / 8] ^= 0x80 >> (bit_index % 8);
+    bytes
+}
+
+/// Return the CMR of the program that is encoded by the given bytes.
+///
+/// Only the program is decoded by the library, see [`ConstructNode::decode`],
+/// so programs with an invalid witness block or sharing decode.
+/// Programs that fail type inference do not decode.
+///
+/// Return `None` if the program does not decode.
+pub fn decode_cmr<A: AsRef<[u8]>>(program_bytes: A) -> Option<Cmr> {
+    let mut bits = BitIter::from(program_bytes.as_ref());
+    ConstructNode::<Elements>::decode(&mut bits)
+        .ok()
+        .map(|program| program.cmr())
+}
+
+/// Run `f` on a thread with a stack of 256 MiB.
+///
+/// Some programs have types or nesting so deep that decoding them overflows the default stack.
+pub fn on_large_stack<T, F>(f: F) -> T
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    std::thread::Builder::new()
+        .stack_size(256 << 20)
+        .spawn(f)
+        .expect("spawn thread")
+        .join()
+        .expect("thread does not panic")
+}
+
+/// Render the given program in the human encoding, for review.
+///
+/// Witness values are not rendered.
+/// Programs whose types or constants are too wide for the human encoding
+/// are rendered as a list of nodes instead, without types.
+///
+/// Return `None` if the program does not decode or if its CMR is not `cmr`.
+pub fn decompile<A: AsRef<[u8]>, B: AsRef<[u8]>>(program_bytes: A, cmr: B) -> Option<String> {
+    let program =
+        RedeemNode::<Elements>::decode(&mut BitIter::from(program_bytes.as_ref())).ok()?;
+    if program.cmr().as_ref() != cmr.as_ref() {
+        return None;
+    }
+
+    // The human encoding pads its columns with a width that must fit into u16
+    let max_width = usize::from(u16::MAX) / 2;
+    let too_wide = program
+        .as_ref()
+        .post_order_iter::<InternalSharing>()
+        .any(|item| {
+            let arrow = item.node.arrow();
+            let mut arrow_writer = BoundedWriter::new(max_width);
+            let word_len = match item.node.inner() {
+                Inner::Word(value) => value.len(),
+                _ => 0,
+            };
+            // Formatting traverses the entire type, which is slow for wide types
+            max_width < arrow.source.bit_width()
+                || max_width < arrow.target.bit_width()
+                || max_width < word_len
+                || write!(arrow_writer, "{} -> {}", arrow.source, arrow.target).is_err()
+        });
+    if too_wide {
+        return Some(program.to_string());
+    }
+
+    let program = program.unfinalize().ok()?;
+    Some(simplicity::human_encoding::Forest::from_program(program).string_serialize())
+}
+
+/// Writer that only counts bytes and that fails beyond a maximum length.
+///
+/// Formatting into this writer stops early, which keeps the formatting of huge types cheap.
+struct BoundedWriter {
+    len: usize,
+    max_len: usize,
+}
+
+impl BoundedWriter {
+    fn new(max_len: usize) -> Self {
+        Self { len: 0, max_len }
+    }
+}
+
+impl fmt::Write for BoundedWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.len += s.len();
+        match self.len <= self.max_len {
+            true => Ok(()),
+            false => Err(fmt::Error),
+        }
+    }
+}
+
+/// Parse a program whose disconnect nodes are filled with continuations.
+///
+/// The human encoding fills a disconnect node with the root that has the same name as the node;
+/// the name of the hole is ignored. This name is already taken by the disconnect node itself,
+/// so `disconnect` maps the name of each disconnect node to the name of its continuation.
+pub fn program_from_string_disconnect(
+    s: &str,
+    witness: &HashMap<Arc<str>, Arc<simplicity::Value>>,
+    disconnect: &[(&str, &str)],
+) -> Arc<RedeemNode<Elements>> {
+    let forest = simplicity::human_encoding::Forest::parse(s).unwrap();
+    let mut roots = forest.roots().clone();
+    for (node_name, continuation_name) in disconnect {
+        let continuation = roots
+            .get(*continuation_name)
+            .expect("continuation is a root")
+            .clone();
+        roots.insert(Arc::from(*node_name), continuation);
+    }
+    roots
+        .get("main")
+        .expect("has main")
+        .to_witness_node(witness, &roots)
+        .finalize()
+        .expect("well-typed and sufficient witness")
+}
+
+/// Assert that two encoded programs have the same CMR but different bytes.
+///
+/// Both encodings commit to the same program, so a canonical decoder accepts at most one of them.
+/// Otherwise, a third party could replace the program in the witness without changing the CMR.
+pub fn assert_same_cmr_different_bytes(a: &(Vec<u8>, Cmr), b: &(Vec<u8>, Cmr)) {
+    assert_eq!(a.1, b.1, "Programs have different CMRs");
+    assert_ne!(a.0, b.0, "Programs have the same encoding");
+}
+
 #[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
 pub enum Case {
     Both,
@@ -142,6 +589,109 @@ impl Case {
     }
 }
 
+/// Return the cost bounds of the given `program`.
+///
+/// The first component is the weight in WU that the budget must have to cover the program cost.
+/// The second component is the number of extra cells (bits) of memory that the program uses.
+pub fn describe_cost(program: &RedeemNode<Elements>) -> (u32, usize) {
+    let bounds = program.bounds();
+    // Cost is opaque: search for the smallest weight that covers it
+    let (mut low, mut high) = (0u32, u32::MAX);
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if bounds.cost.less_equal_weight(mid) {
+            high = mid;
+        } else {
+            low = mid + 1;
+        }
+    }
+    (low, bounds.extra_cells)
+}
+
+/// Return all Elements jets, in the order of their encoding.
+///
+/// Jets are found by decoding every byte string that is a prefix of a jet code.
+pub fn elements_jets() -> Vec<Elements> {
+    let mut jets = BTreeSet::new();
+    let mut prefixes = vec![vec![]];
+
+    while let Some(prefix) = prefixes.pop() {
+        for byte in 0..=u8::MAX {
+            let mut bytes = prefix.clone();
+            bytes.push(byte);
+            match Elements::decode(&mut BitIter::from(bytes.as_slice())) {
+                Ok(jet) => {
+                    jets.insert(jet);
+                }
+                Err(simplicity::decode::Error::EndOfStream) => prefixes.push(bytes),
+                Err(_) => {}
+            }
+        }
+    }
+
+    let mut jets: Vec<_> = jets.into_iter().collect();
+    jets.sort_by_key(|jet| simplicity::write_to_vec(|w| jet.encode(w)));
+    jets
+}
+
+/// Return an expression that produces the all-zero value of the given type.
+///
+/// The expression has source type 1. Sums are produced by `injl`,
+/// so the right type of each sum is left for the context to decide.
+pub fn zero_node(ty: &TypeName) -> Arc<WitnessNode<Elements>> {
+    fn parse(bytes: &mut std::slice::Iter<u8>) -> Arc<WitnessNode<Elements>> {
+        type Node = Arc<WitnessNode<Elements>>;
+        match bytes.next().expect("type name is well-formed") {
+            b'1' => Node::unit(),
+            b'2' => Node::const_word(simplicity::Value::u1(0)),
+            b'c' => Node::const_word(simplicity::Value::u8(0)),
+            b's' => Node::const_word(simplicity::Value::u16(0)),
+            b'i' => Node::const_word(simplicity::Value::u32(0)),
+            b'l' => Node::const_word(simplicity::Value::u64(0)),
+            b'h' => Node::const_word(simplicity::Value::u256_from_slice(&[0; 32])),
+            b'*' => {
+                let left = parse(bytes);
+                let right = parse(bytes);
+                Node::pair(&left, &right).expect("pair of unit sources")
+            }
+            b'+' => {
+                let left = parse(bytes);
+                let _right = parse(bytes);
+                Node::injl(&left)
+            }
+            byte => panic!("Unknown type name: {}", char::from(*byte)),
+        }
+    }
+
+    let mut bytes = ty.0.iter();
+    let node = parse(&mut bytes);
+    assert!(bytes.next().is_none(), "type name is well-formed");
+    node
+}
+
+/// Write the value of a constant word of depth `depth` whose bytes all equal `byte`.
+///
+/// The word has 2^(depth - 1) bits, which is at least one byte.
+/// The output is the same as [`simplicity::encode::encode_value`] of the word,
+/// but the word is not built as a tree of values, which takes seconds for wide words.
+///
+/// Return the number of written bits.
+pub fn encode_const_word_fast<W: io::Write>(
+    depth: usize,
+    byte: u8,
+    w: &mut BitWriter<W>,
+) -> io::Result<usize> {
+    assert!(
+        (4..=32).contains(&depth),
+        "Word of depth {depth} is not made of bytes or too wide"
+    );
+    let n_bytes = 1usize << (depth - 4);
+    for _ in 0..n_bytes {
+        w.write_bits_be(u64::from(byte), 8)?;
+    }
+    Ok(n_bytes * 8)
+}
+
 /// **There is no automatic sharing!**
 ///
 /// **Expressions must be manually shared using `Arc`!**
@@ -154,3 +704,261 @@ pub fn encode_program_empty_witness<W: io::Write>(
     w.flush_all()?;
     Ok(program_bits + 1)
 }
+
+/// Like [`encode_program_empty_witness`], but nodes with the same IMR are shared automatically.
+///
+/// Expressions can be built without threading `Arc` by hand. Subexpressions that contain
+/// witness nodes have no IMR and stay shared by `Arc` only.
+///
+/// ## Panics
+///
+/// The program contains disconnect nodes or fails type inference.
+/// Nodes that are shared by `Arc` have a single type, so a program whose copies of a node
+/// need different types fails the occurs check, even if its unshared encoding is valid.
+pub fn encode_program_empty_witness_shared<W: io::Write>(
+    program: &WitnessNode<Elements>,
+    w: &mut BitWriter<W>,
+) -> io::Result<usize> {
+    /// Drop the witness values and finalize the types of each node.
+    struct ToCommit;
+
+    impl Converter<Witness<Elements>, Commit<Elements>> for ToCommit {
+        type Error = simplicity::types::Error;
+
+        fn convert_witness(
+            &mut self,
+            _: &PostOrderIterItem<&WitnessNode<Elements>>,
+            _: &Option<Arc<simplicity::Value>>,
+        ) -> Result<NoWitness, Self::Error> {
+            Ok(NoWitness)
+        }
+
+        fn convert_disconnect(
+            &mut self,
+            _: &PostOrderIterItem<&WitnessNode<Elements>>,
+            _: Option<&Arc<CommitNode<Elements>>>,
+            _: &Option<Arc<WitnessNode<Elements>>>,
+        ) -> Result<NoDisconnect, Self::Error> {
+            panic!("Disconnect is not supported")
+        }
+
+        fn convert_data(
+            &mut self,
+            data: &PostOrderIterItem<&WitnessNode<Elements>>,
+            inner: Inner<&Arc<CommitNode<Elements>>, Elements, &NoDisconnect, &NoWitness>,
+        ) -> Result<Arc<CommitData<Elements>>, Self::Error> {
+            let inner = inner.map(|node| node.cached_data());
+            CommitData::new(data.node.arrow(), inner).map(Arc::new)
+        }
+    }
+
+    let program = program
+        .convert::<InternalSharing, _, _>(&mut ToCommit)
+        .expect("program has valid types");
+    let program_bits = simplicity::encode::encode_program(&program, w)?;
+    w.write_bit(false)?; // preamble for empty witness
+    w.flush_all()?;
+    Ok(program_bits + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decompile_unit() {
+        let program = program_from_string("main := unit", &HashMap::new());
+        let s = decompile(program.encode_to_vec(), program.cmr()).expect("decodes");
+        assert!(s.contains("unit"), "{s}");
+        assert_eq!(None, decompile(program.encode_to_vec(), Cmr::iden()));
+    }
+
+    #[test]
+    fn decode_cmr_of_programs() {
+        for s in [
+            "main := unit",
+            "main := comp (const 0b1) jet_verify",
+            "main := comp (pair (const 0b0) unit) (assertl unit #{take iden})",
+        ] {
+            let program = program_from_string(s, &HashMap::new());
+            assert_eq!(
+                Some(program.cmr()),
+                decode_cmr(program.encode_to_vec()),
+                "{s}"
+            );
+        }
+        assert_eq!(None, decode_cmr([]));
+        assert_eq!(None, decode_cmr([0x00]));
+    }
+
+    #[test]
+    fn encode_const_word_fast_matches_library() {
+        for byte in [0x00, 0x5a, 0xff] {
+            let mut word = simplicity::Value::u8(byte);
+            for depth in 4..12 {
+                let fast = simplicity::write_to_vec(|w| encode_const_word_fast(depth, byte, w));
+                let slow = simplicity::write_to_vec(|w| simplicity::encode::encode_value(&word, w));
+                assert_eq!(slow, fast, "depth {depth}, byte {byte:#04x}");
+                word = simplicity::Value::prod(word.clone(), word);
+            }
+        }
+    }
+
+    #[test]
+    fn encode_program_empty_witness_shares() {
+        type Node = Arc<WitnessNode<Elements>>;
+        // Two separate `iden` nodes, which are not shared by `Arc`
+        let program = Node::comp(&Node::iden(), &Node::iden()).unwrap();
+        let mut sink = BitWriter::new(io::sink());
+        let unshared_len = encode_program_empty_witness(&program, &mut sink).unwrap();
+        let shared_len = encode_program_empty_witness_shared(&program, &mut sink).unwrap();
+        assert!(shared_len < unshared_len);
+
+        let shared = simplicity::write_to_vec(|w| encode_program_empty_witness_shared(&program, w));
+        assert_eq!(Some(program.cmr()), decode_cmr(shared));
+        // The sharing check is not part of decoding the CMR
+        let unshared = simplicity::write_to_vec(|w| encode_program_empty_witness(&program, w));
+        assert_eq!(Some(program.cmr()), decode_cmr(unshared));
+    }
+
+    #[test]
+    fn corrupt_at_flips_one_bit() {
+        assert_eq!(vec![0x80, 0x00], corrupt_at(&[0x00, 0x00], 0));
+        assert_eq!(vec![0x00, 0x01], corrupt_at(&[0x00, 0x00], 15));
+        assert_eq!(vec![0xff, 0xef], corrupt_at(&[0xff, 0xff], 11));
+    }
+
+    #[test]
+    fn script_roundtrip() {
+        let unit_program = program_from_string("main := unit", &HashMap::new()).encode_to_vec();
+        for bytes in [vec![], vec![0x00], unit_program, vec![0xff; 1 << 20]] {
+            assert_eq!(bytes, from_script(&to_script(&bytes)));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Programs have the same encoding")]
+    fn same_cmr_same_bytes() {
+        let program = (vec![0x24], Cmr::unit());
+        assert_same_cmr_different_bytes(&program, &program);
+    }
+
+    #[test]
+    fn elements_jets_decode() {
+        let jets = elements_jets();
+        assert!(jets.contains(&Elements::Verify));
+        assert!(jets.contains(&Elements::Eq64));
+
+        for jet in jets {
+            let bytes = simplicity::write_to_vec(|w| jet.encode(w));
+            let decoded = Elements::decode(&mut BitIter::from(bytes.as_slice())).unwrap();
+            assert_eq!(jet, decoded);
+        }
+    }
+
+    #[test]
+    fn funding_output_encodes_output_key() {
+        let spend_info = get_spend_info([0; 32], simplicity::leaf_version());
+        let funding_tx = get_funding_tx(
+            elements::confidential::Asset::Null,
+            elements::confidential::Value::Null,
+            get_script_pubkey(&spend_info),
+        );
+        let script_pubkey = funding_tx.output[0].script_pubkey.as_bytes();
+        let output_key = taproot_output_key(&spend_info).serialize();
+
+        assert_eq!(34, script_pubkey.len());
+        assert_eq!(
+            elements::opcodes::all::OP_PUSHNUM_1.into_u8(),
+            script_pubkey[0]
+        );
+        assert_eq!(32, script_pubkey[1]);
+        assert_eq!(output_key, script_pubkey[2..]);
+    }
+
+    #[test]
+    fn minimal_spend_references_funding_output() {
+        let cmr = [0; 32];
+        let (tx, prevouts, index) = minimal_spend_for_cmr(cmr);
+        let spend_info = get_spend_info(cmr, simplicity::leaf_version());
+        let funding_tx = get_funding_tx(
+            elements::confidential::Asset::Null,
+            elements::confidential::Value::Null,
+            get_script_pubkey(&spend_info),
+        );
+
+        assert_eq!(funding_tx.output, prevouts);
+        let outpoint = tx.input[index].previous_output;
+        assert_eq!(funding_tx.txid(), outpoint.txid);
+        assert_eq!(
+            get_script_pubkey(&spend_info),
+            prevouts[outpoint.vout as usize].script_pubkey
+        );
+    }
+
+    #[test]
+    fn key_path_signature_verifies_under_output_key() {
+        let keypair = secp256k1_zkp::Keypair::from_seckey_slice(secp256k1_zkp::SECP256K1, &[1; 32])
+            .expect("valid secret key");
+        let internal_key = keypair.x_only_public_key().0;
+        let spend_info = build_spend_info([0; 32], simplicity::leaf_version(), internal_key, &[]);
+        let (tx, prevouts, index) = spend_output(&spend_info, Spend::default());
+        let genesis_hash = elements::BlockHash::all_zeros();
+        let signature = key_path_signature(
+            &tx,
+            &prevouts,
+            index,
+            &keypair,
+            spend_info.merkle_root(),
+            genesis_hash,
+        );
+
+        let sighash = elements::sighash::SighashCache::new(&tx)
+            .taproot_key_spend_signature_hash(
+                index,
+                &elements::sighash::Prevouts::All(&prevouts),
+                elements::SchnorrSighashType::Default,
+                genesis_hash,
+            )
+            .unwrap();
+        let message = secp256k1_zkp::Message::from_digest(sighash.to_byte_array());
+        let signature = secp256k1_zkp::schnorr::Signature::from_slice(&signature).unwrap();
+        secp256k1_zkp::SECP256K1
+            .verify_schnorr(&signature, &message, &taproot_output_key(&spend_info))
+            .expect("signature is valid");
+    }
+
+    #[test]
+    fn control_block_of_missing_leaf() {
+        let version = simplicity::leaf_version();
+        let spend_info = get_spend_info([0; 32], version);
+        let error = get_control_block([1; 32], version, &spend_info).unwrap_err();
+        let expected = format!(
+            "No leaf of version 0xbe contains script {} (internal key {}, merkle root {})",
+            [1; 32].to_hex(),
+            unspendable_key(),
+            spend_info.merkle_root().unwrap(),
+        );
+        assert_eq!(expected, error.to_string());
+    }
+
+    #[test]
+    fn cost_of_comp_ladder() {
+        let mut s = "id0 := iden\ncp0 := comp id0 id0\n".to_string();
+        for i in 1..=21 {
+            s.push_str(&format!("cp{i} := comp cp{} cp{}\n", i - 1, i - 1));
+        }
+        s.push_str("main := comp cp21 cp21");
+        let program = program_from_string(&s, &HashMap::new());
+
+        let (weight, memory) = describe_cost(&program);
+        println!("cp21 ladder: {} WU, {} extra cells", weight, memory);
+        // Expensive enough to need padding, but cheap enough to stay below MAX_BUDGET
+        assert!(
+            (1_000_000..4_000_050).contains(&weight),
+            "Unexpected weight: {} WU",
+            weight
+        );
+        assert_eq!(0, memory);
+    }
+}
@@ -0,0 +1,95 @@
+//! # Witness construction
+//!
+//! Single source of witness data for both ways of writing programs.
+//!
+//! Human-encoded programs look up witness values by name,
+//! while hand-written programs need the raw witness block of [`BitBuilder`].
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use simplicity::Value;
+
+use crate::bit_encoding::{BitBuilder, Program, Witness};
+
+/// Builder of named witness values.
+///
+/// Values are stored in insertion order.
+/// The raw witness block lists values in this order,
+/// so values must be inserted in the order in which their `witness` nodes
+/// appear in the canonical encoding of the program.
+#[derive(Debug, Clone, Default)]
+pub struct WitnessBuilder {
+    values: Vec<(Arc<str>, Arc<Value>)>,
+}
+
+impl WitnessBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a witness value under the given name.
+    ///
+    /// ## Panics
+    ///
+    /// There is already a value with the same name.
+    pub fn value<A: Into<Arc<str>>>(mut self, name: A, value: Arc<Value>) -> Self {
+        let name = name.into();
+        assert!(
+            self.values.iter().all(|(other, _)| other != &name),
+            "Duplicate witness name: {}",
+            name
+        );
+        self.values.push((name, value));
+        self
+    }
+
+    /// Return the witness map for [`crate::util::program_from_string`].
+    pub fn to_map(&self) -> HashMap<Arc<str>, Arc<Value>> {
+        self.values.iter().cloned().collect()
+    }
+
+    /// Write the witness values as the raw witness block after the given program.
+    pub fn witness_block(&self, builder: BitBuilder<Program>) -> BitBuilder<Witness> {
+        builder.witness_block(self.values.iter().map(|(_, value)| value.as_ref()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util;
+    use simplicity::jet::Elements;
+    use simplicity::{BitIter, RedeemNode};
+
+    #[test]
+    fn renderings_agree() {
+        let witness = WitnessBuilder::new()
+            .value("wit1", Value::u8(0xca))
+            .value("wit2", Value::u8(0xfe));
+        let s = "
+            wit1 := witness
+            wit2 := witness
+            main := comp (comp (pair wit1 wit2) jet_lt_8) jet_verify
+        ";
+        let human_bytes = util::program_from_string(s, &witness.to_map()).encode_to_vec();
+
+        let builder = BitBuilder::program_preamble(7)
+            .witness()
+            .witness()
+            .pair(2, 1)
+            .named_jet(Elements::Lt8)
+            .comp(2, 1)
+            .named_jet(Elements::Verify)
+            .comp(2, 1);
+        let raw_bytes = witness.witness_block(builder).program_finished();
+
+        let human_program =
+            RedeemNode::<Elements>::decode(&mut BitIter::from(human_bytes.into_iter())).unwrap();
+        let raw_program =
+            RedeemNode::<Elements>::decode(&mut BitIter::from(raw_bytes.into_iter())).unwrap();
+        assert_eq!(human_program.cmr(), raw_program.cmr());
+        assert_eq!(human_program.imr(), raw_program.imr());
+        assert_eq!(human_program.encode_to_vec(), raw_program.encode_to_vec());
+    }
+}